@@ -0,0 +1,353 @@
+use std::fs::read;
+use std::io::{stdin, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+use clap::{Args, Parser, Subcommand};
+use futures::stream::{iter, unfold, BoxStream};
+use futures::StreamExt as _;
+use serde_json::{Deserializer, Value};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::task::{spawn_blocking, JoinHandle};
+use trapeze::reflect::{DescriptorPool, DynamicClient};
+use trapeze::{Client, ClientExt as _};
+use trapeze_codegen::Config;
+
+/// A command line client for ttrpc services
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Call a method and print the responses as JSON, one per line
+    Call(CallArgs),
+
+    /// List the services and methods described by the loaded protos
+    List(ProtoArgs),
+}
+
+#[derive(Args)]
+struct ProtoArgs {
+    /// A `.proto` file describing the service
+    #[arg(short, long = "proto", value_name = "FILE")]
+    protos: Vec<PathBuf>,
+
+    /// A directory in which to search for imports.
+    /// Defaults to the directories of the `.proto` files.
+    #[arg(short = 'I', long = "include", value_name = "DIR")]
+    includes: Vec<PathBuf>,
+
+    /// A binary encoded `FileDescriptorSet` describing the service
+    #[arg(short, long = "descriptor-set", value_name = "FILE")]
+    descriptor_sets: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct CallArgs {
+    #[command(flatten)]
+    protos: ProtoArgs,
+
+    /// The address of the server, e.g., `unix:///run/agent.sock`, `tcp://127.0.0.1:1234` or `vsock://3:1024`
+    #[arg(short, long)]
+    address: String,
+
+    /// Metadata to send with the request
+    #[arg(short, long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    metadata: Vec<(String, String)>,
+
+    /// Timeout of the request, e.g., `500ms` or `10s`
+    #[arg(short, long, value_parser = humantime::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// The method to call, e.g., `ttrpc.test.streaming.Streaming/Echo`
+    method: String,
+
+    /// The JSON request.
+    /// Client streaming methods accept a sequence of JSON values.
+    /// If omitted or `-`, the requests are read from stdin.
+    data: Option<String>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match cli.command {
+        Command::Call(args) => call(args).await,
+        Command::List(args) => list(&args),
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn call(args: CallArgs) -> Result<()> {
+    let pool = load_pool(&args.protos)?;
+
+    let (service, method) = split_method(&args.method)?;
+
+    let client = Client::connect(&args.address)
+        .await
+        .with_context(|| format!("Failed to connect to `{}`", args.address))?
+        .with_metadata(args.metadata.as_slice())
+        .with_timeout(args.timeout);
+
+    let client = DynamicClient::new(client, pool);
+    client.method(service, method)?;
+
+    let (input, reader) = match args.data.as_deref() {
+        None | Some("-") => {
+            let (input, reader) = read_input(stdin());
+            (input, Some(reader))
+        }
+        Some(data) => (iter(parse_json_values(data.as_bytes())?).boxed(), None),
+    };
+
+    let mut outputs = client.call_json(service, method, input).boxed();
+    while let Some(output) = outputs.next().await {
+        match output {
+            Ok(output) => println!("{output}"),
+            Err(status) => {
+                // Errors reading the input take precedence, as they probably caused the call to fail
+                finish(reader).await?;
+                return Err(status.into());
+            }
+        }
+    }
+
+    finish(reader).await
+}
+
+fn split_method(method: &str) -> Result<(&str, &str)> {
+    method
+        .trim_start_matches('/')
+        .split_once('/')
+        .with_context(|| format!("Invalid method `{method}`, expected `<service>/<method>`"))
+}
+
+fn list(args: &ProtoArgs) -> Result<()> {
+    let pool = load_pool(args)?;
+    for service in pool.services() {
+        println!("{}", service.full_name());
+        for method in service.methods() {
+            let stream = |streaming| if streaming { "stream " } else { "" };
+            println!(
+                "  {}({}{}) returns ({}{})",
+                method.name(),
+                stream(method.is_client_streaming()),
+                method.input().full_name(),
+                stream(method.is_server_streaming()),
+                method.output().full_name(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn load_pool(args: &ProtoArgs) -> Result<DescriptorPool> {
+    let mut pool = DescriptorPool::new();
+
+    for path in &args.descriptor_sets {
+        let bytes = read(path).with_context(|| format!("Failed to read {path:?}"))?;
+        pool.decode_file_descriptor_set(bytes.as_slice())
+            .with_context(|| format!("Invalid descriptor set {path:?}"))?;
+    }
+
+    if !args.protos.is_empty() {
+        let mut includes = args.includes.clone();
+        if includes.is_empty() {
+            includes = args
+                .protos
+                .iter()
+                .filter_map(|p| p.parent().map(ToOwned::to_owned))
+                .collect();
+            includes.sort_unstable();
+            includes.dedup();
+        }
+        let fds = Config::new()
+            .load_fds(&args.protos, &includes)
+            .context("Failed to compile protos")?;
+        pool.add_file_descriptor_set(fds)
+            .context("Invalid protos")?;
+    }
+
+    if pool.services().len() == 0 {
+        bail!("No services found, use `--proto` or `--descriptor-set` to describe the service");
+    }
+
+    Ok(pool)
+}
+
+// The task reading the input of a call
+struct InputReader {
+    task: JoinHandle<Result<()>>,
+    // Whether the call consumed the whole input
+    ended: Arc<AtomicBool>,
+}
+
+// Reads a sequence of JSON values from `reader` as they become available
+fn read_input(reader: impl Read + Send + 'static) -> (BoxStream<'static, String>, InputReader) {
+    let (tx, rx) = unbounded_channel();
+    let task = spawn_blocking(move || {
+        for value in Deserializer::from_reader(reader).into_iter::<Value>() {
+            let value = value.context("Invalid JSON input")?;
+            let _ = tx.send(value.to_string());
+        }
+        Ok(())
+    });
+    let ended = Arc::new(AtomicBool::new(false));
+    let input = unfold((rx, ended.clone()), |(mut rx, ended)| async move {
+        let Some(value) = rx.recv().await else {
+            ended.store(true, Ordering::Relaxed);
+            return None;
+        };
+        Some((value, (rx, ended)))
+    });
+    (input.boxed(), InputReader { task, ended })
+}
+
+// Returns the error reading the input, if any.
+// The reader is not awaited if the call finished without consuming the whole input,
+// e.g., unary calls only take the first value, as it might be blocked on stdin.
+async fn finish(reader: Option<InputReader>) -> Result<()> {
+    let Some(InputReader { task, ended }) = reader else {
+        return Ok(());
+    };
+    if ended.load(Ordering::Relaxed) || task.is_finished() {
+        task.await??;
+    }
+    Ok(())
+}
+
+fn parse_json_values(data: &[u8]) -> Result<Vec<String>> {
+    Deserializer::from_slice(data)
+        .into_iter::<Value>()
+        .map(|value| Ok(value.context("Invalid JSON input")?.to_string()))
+        .collect()
+}
+
+fn parse_key_value(kv: &str) -> Result<(String, String)> {
+    let (key, value) = kv
+        .split_once('=')
+        .with_context(|| format!("Invalid metadata `{kv}`, expected `<key>=<value>`"))?;
+    Ok((key.into(), value.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use clap::CommandFactory as _;
+
+    use super::*;
+
+    fn parse_call(args: &[&str]) -> Result<CallArgs, clap::Error> {
+        let args = ["trapeze-cli", "call"].iter().chain(args);
+        match Cli::try_parse_from(args)?.command {
+            Command::Call(args) => Ok(args),
+            Command::List(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn validates_the_command_line() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_call_arguments() {
+        let args = parse_call(&[
+            "--address",
+            "unix:///run/agent.sock",
+            "-m",
+            "key=value",
+            "-m",
+            "filter=a=b",
+            "--timeout",
+            "500ms",
+            "-p",
+            "agent.proto",
+            "agent.Agent/Status",
+            r#"{"verbose":true}"#,
+        ])
+        .unwrap();
+
+        assert_eq!(args.address, "unix:///run/agent.sock");
+        assert_eq!(
+            args.metadata,
+            [
+                ("key".into(), "value".into()),
+                ("filter".into(), "a=b".into())
+            ]
+        );
+        assert_eq!(args.timeout, Some(Duration::from_millis(500)));
+        assert_eq!(args.protos.protos, [PathBuf::from("agent.proto")]);
+        assert_eq!(args.method, "agent.Agent/Status");
+        assert_eq!(args.data.as_deref(), Some(r#"{"verbose":true}"#));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let address = ["--address", "unix:///run/agent.sock"];
+
+        let missing_value = [&address[..], &["-m", "key", "agent.Agent/Status"]].concat();
+        assert!(parse_call(&missing_value).is_err());
+
+        let bad_timeout = [&address[..], &["-t", "soon", "agent.Agent/Status"]].concat();
+        assert!(parse_call(&bad_timeout).is_err());
+
+        assert!(parse_call(&["agent.Agent/Status"]).is_err());
+    }
+
+    #[test]
+    fn splits_methods() {
+        assert_eq!(
+            split_method("agent.Agent/Status").unwrap(),
+            ("agent.Agent", "Status")
+        );
+        assert_eq!(
+            split_method("/agent.Agent/Status").unwrap(),
+            ("agent.Agent", "Status")
+        );
+        assert!(split_method("agent.Agent").is_err());
+    }
+
+    #[test]
+    fn parses_sequences_of_json_values() {
+        let values = parse_json_values(br#"{"a": 1} {"b": [2]}  3"#).unwrap();
+        assert_eq!(values, [r#"{"a":1}"#, r#"{"b":[2]}"#, "3"]);
+
+        assert!(parse_json_values(br#"{"a": 1} {"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_json_input() {
+        let (input, reader) = read_input(Cursor::new(r#"{"a": 1} {"b": 2}"#));
+
+        let values: Vec<_> = input.collect().await;
+        assert_eq!(values, [r#"{"a":1}"#, r#"{"b":2}"#]);
+        finish(Some(reader)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_json_input() {
+        let (input, reader) = read_input(Cursor::new(r#"{"a": 1} {"b"#));
+
+        // the input ends at the first invalid value
+        let values: Vec<_> = input.collect().await;
+        assert_eq!(values, [r#"{"a":1}"#]);
+        let err = finish(Some(reader)).await.unwrap_err();
+        assert!(format!("{err:#}").starts_with("Invalid JSON input"));
+    }
+}
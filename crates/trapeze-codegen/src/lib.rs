@@ -1,6 +1,10 @@
+use std::cell::RefCell;
 use std::io::Result;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
+use std::rc::Rc;
+
+use prost_build::{Service, ServiceGenerator};
 
 #[doc(hidden)]
 pub mod prost_build {
@@ -60,7 +64,10 @@ pub fn compile_protos(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]
 /// Configuration options for Protobuf code generation.
 ///
 /// This configuration builder can be used to set non-default code generation options.
-pub struct Config(prost_build::Config);
+pub struct Config {
+    inner: prost_build::Config,
+    generators: Rc<RefCell<Generators>>,
+}
 
 impl Config {
     /// Creates a new code generator configuration with default options.
@@ -68,13 +75,65 @@ impl Config {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Generate service methods that take a `trapeze::Request<T>` instead of the bare input message.
+    ///
+    /// The request wrapper carries the metadata, deadline, peer address and cancellation token of
+    /// the call, so that server implementations don't need to rely on `trapeze::get_context()`.
+    /// Clients can use it to set per-call metadata and timeout.
+    ///
+    /// Defaults to `false`.
+    pub fn request_wrappers(&mut self, enable: bool) -> &mut Self {
+        self.generators.borrow_mut().ttrpc.request_wrappers = enable;
+        self
+    }
+
+    /// Generate server streaming methods that send their responses to a `trapeze::ResponseSink<T>`,
+    /// instead of returning a stream of responses.
+    ///
+    /// This is convenient when responses come from callbacks or from several producers.
+    /// Sending to the sink waits while earlier responses are still being written,
+    /// and fails once the client is no longer receiving responses.
+    /// Clients create the sink, and the stream of its responses, with `trapeze::ResponseSink::channel()`.
+    ///
+    /// Defaults to `false`.
+    pub fn response_sinks(&mut self, enable: bool) -> &mut Self {
+        self.generators.borrow_mut().ttrpc.response_sinks = enable;
+        self
+    }
+
+    /// Sets a service generator that generates more code for each service, after the ttrpc code,
+    /// e.g., to also generate gRPC services.
+    ///
+    /// Unlike `prost_build::Config::service_generator`, this doesn't replace the ttrpc service
+    /// generator, and the options of this configuration keep applying to it.
+    pub fn service_generator(&mut self, service_generator: Box<dyn ServiceGenerator>) -> &mut Self {
+        self.generators.borrow_mut().user = Some(service_generator);
+        self
+    }
+
+    /// Generate `bytes::Bytes` fields instead of `Vec<u8>` for the protobuf `bytes` fields matching `paths`,
+    /// e.g., `["."]` for all fields. See `prost_build::Config::bytes` for the syntax of paths.
+    ///
+    /// `Bytes` fields are decoded without copying them out of the received frame,
+    /// and large ones are written to the connection without copying them into the frame.
+    /// This avoids copies of large payloads, e.g., file or image chunks.
+    pub fn bytes<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.inner.bytes(paths);
+        self
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let mut cfg = prost_build::Config::new();
-        cfg.service_generator(Box::new(TtrpcServiceGenerator));
-        Self(cfg)
+        let generators = Rc::default();
+        let mut inner = prost_build::Config::new();
+        inner.service_generator(Box::new(ConfigServiceGenerator(Rc::clone(&generators))));
+        Self { inner, generators }
     }
 }
 
@@ -82,12 +141,53 @@ impl Deref for Config {
     type Target = prost_build::Config;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.inner
     }
 }
 
 impl DerefMut for Config {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.inner
+    }
+}
+
+// The service generators of a `Config`
+#[derive(Default)]
+struct Generators {
+    ttrpc: TtrpcServiceGenerator,
+    // Set with `Config::service_generator`
+    user: Option<Box<dyn ServiceGenerator>>,
+}
+
+// The service generator installed by a `Config`, sharing its generators with it,
+// so that changing the options of the `Config` doesn't need to replace it
+struct ConfigServiceGenerator(Rc<RefCell<Generators>>);
+
+impl ServiceGenerator for ConfigServiceGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        let Generators { ttrpc, user } = &mut *self.0.borrow_mut();
+        match user {
+            Some(user) => {
+                ttrpc.generate(service.clone(), buf);
+                user.generate(service, buf);
+            }
+            None => ttrpc.generate(service, buf),
+        }
+    }
+
+    fn finalize(&mut self, buf: &mut String) {
+        let Generators { ttrpc, user } = &mut *self.0.borrow_mut();
+        ttrpc.finalize(buf);
+        if let Some(user) = user {
+            user.finalize(buf);
+        }
+    }
+
+    fn finalize_package(&mut self, package: &str, buf: &mut String) {
+        let Generators { ttrpc, user } = &mut *self.0.borrow_mut();
+        ttrpc.finalize_package(package, buf);
+        if let Some(user) = user {
+            user.finalize_package(package, buf);
+        }
     }
 }
@@ -4,17 +4,53 @@ use prost_build::{Comments, Method, Service, ServiceGenerator};
 
 /// A service generator that takes a service descriptor and generates Rust code for a `ttrpc` service.
 ///
-/// It generates a trait describing methods of the service and implements the trait for a `trapeze::Client`.
+/// It generates a trait describing methods of the service and implements the trait for
+/// `trapeze::Client`, `trapeze::ClientPool` and `trapeze::balancer::Balancer`.
 /// To implement a server, users should implement the trait on their own objects.
-pub struct TtrpcServiceGenerator;
+///
+/// `Config` already generates services with it. To use it with a `prost_build::Config`,
+/// create it with `TtrpcServiceGenerator::new()`.
+/// This is a breaking change from earlier versions, where it was a unit struct
+/// and `TtrpcServiceGenerator` itself was the generator.
+#[derive(Clone, Default)]
+pub struct TtrpcServiceGenerator {
+    pub(crate) request_wrappers: bool,
+    pub(crate) response_sinks: bool,
+}
+
+impl TtrpcServiceGenerator {
+    /// Creates a service generator with the default options, as used by `Config::new`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate methods that take a `trapeze::Request<T>` instead of the bare input message.
+    /// See `Config::request_wrappers`.
+    #[must_use]
+    pub fn request_wrappers(mut self, enable: bool) -> Self {
+        self.request_wrappers = enable;
+        self
+    }
+
+    /// Generate server streaming methods that send their responses to a `trapeze::ResponseSink<T>`
+    /// instead of returning a stream.
+    /// See `Config::response_sinks`.
+    #[must_use]
+    pub fn response_sinks(mut self, enable: bool) -> Self {
+        self.response_sinks = enable;
+        self
+    }
+}
 
 impl ServiceGenerator for TtrpcServiceGenerator {
     fn generate(&mut self, service: Service, buf: &mut String) {
         let mut substitutions = service_substitutions(&service);
 
-        let make_client_method = |m| make_client_method(substitutions.clone(), m);
-        let make_trait_method = |m| make_trait_method(substitutions.clone(), m);
-        let make_dispatch_branch = |m| make_dispatch_branch(substitutions.clone(), m);
+        let options = self.clone();
+        let make_client_method = |m| make_client_method(substitutions.clone(), &options, m);
+        let make_trait_method = |m| make_trait_method(substitutions.clone(), &options, m);
+        let make_dispatch_branch = |m| make_dispatch_branch(substitutions.clone(), &options, m);
 
         let methods = service.methods;
 
@@ -32,17 +68,22 @@ impl ServiceGenerator for TtrpcServiceGenerator {
     }
 }
 
-fn make_trait_method(mut substitutions: HashMap<&'static str, String>, method: &Method) -> String {
-    substitutions.extend(method_substitutions(method));
+fn make_trait_method(
+    mut substitutions: HashMap<&'static str, String>,
+    options: &TtrpcServiceGenerator,
+    method: &Method,
+) -> String {
+    substitutions.extend(method_substitutions(options, method));
 
     replace(include_str!("../templates/trait_method.rs"), substitutions)
 }
 
 fn make_dispatch_branch(
     mut substitutions: HashMap<&'static str, String>,
+    options: &TtrpcServiceGenerator,
     method: &Method,
 ) -> String {
-    substitutions.extend(method_substitutions(method));
+    substitutions.extend(method_substitutions(options, method));
 
     replace(
         include_str!("../templates/dispatch_branch.rs"),
@@ -50,10 +91,20 @@ fn make_dispatch_branch(
     )
 }
 
-fn make_client_method(mut substitutions: HashMap<&'static str, String>, method: &Method) -> String {
-    substitutions.extend(method_substitutions(method));
+fn make_client_method(
+    mut substitutions: HashMap<&'static str, String>,
+    options: &TtrpcServiceGenerator,
+    method: &Method,
+) -> String {
+    substitutions.extend(method_substitutions(options, method));
+
+    let template = if options.request_wrappers {
+        include_str!("../templates/client_method_request.rs")
+    } else {
+        include_str!("../templates/client_method.rs")
+    };
 
-    replace(include_str!("../templates/client_method.rs"), substitutions)
+    replace(template, substitutions)
 }
 
 fn service_substitutions(service: &Service) -> HashMap<&'static str, String> {
@@ -66,7 +117,10 @@ fn service_substitutions(service: &Service) -> HashMap<&'static str, String> {
     substitutions
 }
 
-fn method_substitutions(method: &Method) -> HashMap<&'static str, String> {
+fn method_substitutions(
+    options: &TtrpcServiceGenerator,
+    method: &Method,
+) -> HashMap<&'static str, String> {
     let mut substitutions = HashMap::default();
     let Method {
         name,
@@ -80,6 +134,7 @@ fn method_substitutions(method: &Method) -> HashMap<&'static str, String> {
     } = method;
 
     let input_name = camel2snake(input_type);
+    let response_sink = options.response_sinks && *server_streaming;
 
     let wrapper = match (*client_streaming, *server_streaming) {
         (false, false) => "UnaryMethod",
@@ -101,19 +156,55 @@ fn method_substitutions(method: &Method) -> HashMap<&'static str, String> {
         input_type.clone()
     };
 
-    let output_type = if *server_streaming {
+    let input_type = if options.request_wrappers {
+        format!("trapeze::Request<{input_type}>")
+    } else {
+        input_type
+    };
+
+    let input = if options.request_wrappers {
+        "trapeze::__codegen_prelude::server_request(input)"
+    } else {
+        "input"
+    };
+
+    let sink_param = if response_sink {
+        format!("response_sink: trapeze::ResponseSink<{output_type}>,")
+    } else {
+        String::new()
+    };
+
+    let output_type = if response_sink {
+        fallible_future_for("()")
+    } else if *server_streaming {
         fallible_stream_for(output_type)
     } else {
         fallible_future_for(output_type)
     };
 
-    let output_handler = if *server_streaming {
-        stream_handler(name)
+    let output_handler = if response_sink {
+        sink_handler(name, input)
+    } else if *server_streaming {
+        stream_handler(name, input)
+    } else {
+        future_handler(name, input)
+    };
+
+    let direct_client_handler = if response_sink {
+        client_sink_call("self", request_handler, &input_name)
+    } else {
+        client_call("self", request_handler, &input_name)
+    };
+
+    let client_handler = if response_sink {
+        client_sink_handler(request_handler, &input_name)
+    } else if *server_streaming {
+        client_stream_handler(request_handler, &input_name)
     } else {
-        future_handler(name)
+        client_future_handler(request_handler, &input_name)
     };
 
-    let not_found = if *server_streaming {
+    let not_found = if *server_streaming && !response_sink {
         not_found_stream()
     } else {
         not_found_future()
@@ -125,9 +216,12 @@ fn method_substitutions(method: &Method) -> HashMap<&'static str, String> {
     substitutions.insert("method_input_name", input_name);
     substitutions.insert("method_input_type", input_type);
     substitutions.insert("method_output_type", output_type);
+    substitutions.insert("method_sink_param", sink_param);
     substitutions.insert("method_wrapper", wrapper.to_string());
     substitutions.insert("method_request_handler", request_handler.to_string());
     substitutions.insert("method_output_handler", output_handler);
+    substitutions.insert("method_direct_client_handler", direct_client_handler);
+    substitutions.insert("method_client_handler", client_handler);
     substitutions.insert("method_not_found", not_found);
     substitutions
 }
@@ -162,12 +256,40 @@ fn replace(src: impl Into<String>, substitutions: HashMap<&'static str, String>)
     src
 }
 
-fn future_handler(method_name: &str) -> String {
-    format!("async move {{ target.{method_name}(input).await }}")
+fn future_handler(method_name: &str, input: &str) -> String {
+    format!("async move {{ target.{method_name}({input}).await }}")
+}
+
+fn stream_handler(method_name: &str, input: &str) -> String {
+    format!("trapeze::stream::stream! {{ for await value in target.{method_name}({input}) {{ yield value; }} }}")
+}
+
+fn sink_handler(method_name: &str, input: &str) -> String {
+    format!("trapeze::__codegen_prelude::sink_stream(move |response_sink| async move {{ target.{method_name}({input}, response_sink).await }})")
+}
+
+fn client_future_handler(request_handler: &str, input_name: &str) -> String {
+    let call = client_call("&client", request_handler, input_name);
+    format!("async move {{ {call}.await }}")
+}
+
+fn client_stream_handler(request_handler: &str, input_name: &str) -> String {
+    let call = client_call("&client", request_handler, input_name);
+    format!("trapeze::stream::stream! {{ for await value in {call} {{ yield value; }} }}")
+}
+
+fn client_sink_handler(request_handler: &str, input_name: &str) -> String {
+    let call = client_sink_call("&client", request_handler, input_name);
+    format!("async move {{ {call}.await }}")
+}
+
+fn client_call(client: &str, request_handler: &str, input_name: &str) -> String {
+    format!("trapeze::__codegen_prelude::RequestHandler::{request_handler}({client}, service, method, {input_name})")
 }
 
-fn stream_handler(method_name: &str) -> String {
-    format!("trapeze::stream::stream! {{ for await value in target.{method_name}(input) {{ yield value; }} }}")
+fn client_sink_call(client: &str, request_handler: &str, input_name: &str) -> String {
+    let call = client_call(client, request_handler, input_name);
+    format!("trapeze::__codegen_prelude::forward_to_sink({call}, response_sink)")
 }
 
 fn not_found_future() -> String {
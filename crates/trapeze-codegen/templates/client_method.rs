@@ -1,3 +1,5 @@
-fn __method_name__(&self, __method_input_name__: __method_input_type__) -> __method_output_type__ {
-    trapeze::__codegen_prelude::RequestHandler::__method_request_handler__(self, "__service_package__.__service_proto_name__".into(), "__method_proto_name__".into(), __method_input_name__)
+fn __method_name__(&self, __method_input_name__: __method_input_type__, __method_sink_param__) -> __method_output_type__ {
+    let service = "__service_package__.__service_proto_name__".into();
+    let method = "__method_proto_name__".into();
+    __method_direct_client_handler__
 }
@@ -0,0 +1,7 @@
+fn __method_name__(&self, __method_input_name__: __method_input_type__, __method_sink_param__) -> __method_output_type__ {
+    let (context, __method_input_name__) = __method_input_name__.into_parts();
+    let client = trapeze::__codegen_prelude::with_request_context(self, context);
+    let service = "__service_package__.__service_proto_name__".into();
+    let method = "__method_proto_name__".into();
+    __method_client_handler__
+}
@@ -22,3 +22,14 @@ pub trait __service_name__: Send + Sync + 'static {
 impl __service_name__ for trapeze::Client {
     __client_methods__
 }
+
+impl __service_name__ for trapeze::ClientPool {
+    __client_methods__
+}
+
+impl<C> __service_name__ for trapeze::balancer::Balancer<C>
+where
+    C: trapeze::__codegen_prelude::RequestHandler + trapeze::ClientExt + Send + Sync + 'static,
+{
+    __client_methods__
+}
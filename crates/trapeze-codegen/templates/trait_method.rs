@@ -3,6 +3,7 @@ __method_comments__
 fn __method_name__(
     &self,
     __method_input_name__: __method_input_type__,
+    __method_sink_param__
 ) -> __method_output_type__ {
     let not_found = trapeze::Status {
         code: trapeze::Code::NotFound as i32,
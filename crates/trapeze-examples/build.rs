@@ -0,0 +1,43 @@
+use std::env;
+use std::io::Result;
+use std::path::PathBuf;
+
+use trapeze_codegen::prost_build::{Service, ServiceGenerator};
+
+// Generates the streaming service with the non-default codegen options, for the tests
+fn main() -> Result<()> {
+    println!("cargo:rerun-if-changed=protos/streaming.proto");
+
+    trapeze_codegen::Config::new()
+        .service_generator(Box::new(ServiceNames))
+        .request_wrappers(true)
+        .include_file("mod.rs")
+        .out_dir(out_dir("request_wrappers")?)
+        .compile_protos(&["protos/streaming.proto"], &["protos"])?;
+
+    trapeze_codegen::Config::new()
+        .response_sinks(true)
+        .include_file("mod.rs")
+        .out_dir(out_dir("response_sinks")?)
+        .compile_protos(&["protos/streaming.proto"], &["protos"])
+}
+
+// A directory for the generated code of each set of options
+fn out_dir(name: &str) -> Result<PathBuf> {
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join(name);
+    std::fs::create_dir_all(&out_dir)?;
+    Ok(out_dir)
+}
+
+// Generates a constant with the full name of each service, next to the ttrpc code
+struct ServiceNames;
+
+impl ServiceGenerator for ServiceNames {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        let constant = service.name.to_uppercase();
+        let name = format!("{}.{}", service.package, service.proto_name);
+        buf.push_str(&format!(
+            "pub const {constant}_SERVICE_NAME: &str = \"{name}\";\n"
+        ));
+    }
+}
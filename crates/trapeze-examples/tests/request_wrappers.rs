@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use futures::{stream, StreamExt as _};
+use tokio::io::duplex;
+use trapeze::{Client, Request, Result, ServerConnection};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/request_wrappers/mod.rs"));
+}
+
+use generated::ttrpc::test::streaming::*;
+
+#[derive(Clone, Default)]
+struct Services;
+
+impl Streaming for Services {
+    async fn echo(&self, request: Request<EchoPayload>) -> Result<EchoPayload> {
+        let who = request.metadata().get("who").cloned().unwrap_or_default();
+        let has_deadline = request.deadline().is_some();
+        let mut echo_payload = request.into_inner();
+        echo_payload.seq += 1;
+        echo_payload.msg = format!("{} {who:?} {has_deadline}", echo_payload.msg);
+        Ok(echo_payload)
+    }
+
+    async fn sum_stream(
+        &self,
+        request: Request<impl futures::Stream<Item = Part> + Send>,
+    ) -> Result<Sum> {
+        let parts: Vec<_> = request.into_inner().collect().await;
+        Ok(Sum {
+            sum: parts.iter().map(|part| part.add).sum(),
+            num: parts.len() as i32,
+        })
+    }
+
+    fn divide_stream(
+        &self,
+        request: Request<Sum>,
+    ) -> impl futures::Stream<Item = Result<Part>> + Send {
+        let Sum { sum, num } = request.into_inner();
+        stream::repeat(Part { add: sum / num })
+            .take(num as usize)
+            .map(Ok)
+    }
+}
+
+fn connect() -> Client {
+    let (client, server) = duplex(1 << 16);
+    let mut server = ServerConnection::new(server);
+    server.register(Streaming(Services));
+    tokio::spawn(async move { server.start().await });
+    Client::new(client)
+}
+
+#[tokio::test]
+async fn sends_request_context() {
+    let client = connect();
+
+    let request = Request::new(EchoPayload {
+        seq: 1,
+        msg: "hello".into(),
+    });
+    let response = client.echo(request).await.unwrap();
+    assert_eq!(response.seq, 2);
+    assert_eq!(response.msg, "hello [] false");
+
+    let request = Request::new(EchoPayload {
+        seq: 1,
+        msg: "hello".into(),
+    })
+    .with_metadata([("who", "test")])
+    .with_timeout(Duration::from_secs(10));
+    let response = client.echo(request).await.unwrap();
+    assert_eq!(response.msg, "hello [\"test\"] true");
+}
+
+#[tokio::test]
+async fn wraps_streaming_methods() {
+    let client = connect();
+
+    let parts = stream::iter([1, 2, 3].map(|add| Part { add }));
+    let sum = client.sum_stream(Request::new(parts)).await.unwrap();
+    assert_eq!(sum, Sum { sum: 6, num: 3 });
+
+    let parts: Vec<_> = client
+        .divide_stream(Request::new(Sum { sum: 6, num: 3 }))
+        .map(|part| part.unwrap().add)
+        .collect()
+        .await;
+    assert_eq!(parts, [2, 2, 2]);
+}
+
+#[test]
+fn generates_code_of_other_service_generators() {
+    assert_eq!(STREAMING_SERVICE_NAME, "ttrpc.test.streaming.Streaming");
+}
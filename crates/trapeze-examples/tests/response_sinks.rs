@@ -0,0 +1,92 @@
+use futures::{stream, StreamExt as _};
+use tokio::io::duplex;
+use trapeze::{Client, Code, ResponseSink, Result, ServerConnection, Status};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/response_sinks/mod.rs"));
+}
+
+use generated::ttrpc::test::streaming::*;
+
+#[derive(Clone, Default)]
+struct Services;
+
+impl Streaming for Services {
+    async fn echo_stream(
+        &self,
+        input: impl futures::Stream<Item = EchoPayload> + Send,
+        response_sink: ResponseSink<EchoPayload>,
+    ) -> Result<()> {
+        let mut input = std::pin::pin!(input);
+        while let Some(mut echo_payload) = input.next().await {
+            echo_payload.seq += 1;
+            response_sink.send(echo_payload).await?;
+        }
+        Ok(())
+    }
+
+    async fn divide_stream(&self, sum: Sum, response_sink: ResponseSink<Part>) -> Result<()> {
+        let Sum { sum, num } = sum;
+        if num == 0 {
+            return Err(Status::new(Code::InvalidArgument, "Division by zero"));
+        }
+        for _ in 0..num {
+            response_sink.send(Part { add: sum / num }).await?;
+        }
+
+        // closing the sink finishes the call, even with clones of it left
+        let clone = response_sink.clone();
+        tokio::spawn(async move { clone.closed().await });
+        response_sink.close(Status::new(Code::Ok, "")).await;
+        Ok(())
+    }
+}
+
+fn connect() -> Client {
+    let (client, server) = duplex(1 << 16);
+    let mut server = ServerConnection::new(server);
+    server.register(Streaming(Services));
+    tokio::spawn(async move { server.start().await });
+    Client::new(client)
+}
+
+#[tokio::test]
+async fn sends_responses_to_sinks() {
+    let client = connect();
+
+    let (sink, parts) = ResponseSink::channel();
+    let call = client.divide_stream(Sum { sum: 6, num: 3 }, sink);
+    let (res, parts) = tokio::join!(call, parts.collect::<Vec<_>>());
+    res.unwrap();
+    assert_eq!(
+        parts,
+        [
+            Ok(Part { add: 2 }),
+            Ok(Part { add: 2 }),
+            Ok(Part { add: 2 })
+        ]
+    );
+
+    let (sink, echoes) = ResponseSink::channel();
+    let input = stream::iter([1, 2].map(|seq| EchoPayload {
+        seq,
+        msg: "hello".into(),
+    }));
+    let call = client.echo_stream(input, sink);
+    let (res, echoes) = tokio::join!(call, echoes.collect::<Vec<_>>());
+    res.unwrap();
+    let seqs: Vec<_> = echoes.into_iter().map(|echo| echo.unwrap().seq).collect();
+    assert_eq!(seqs, [2, 3]);
+}
+
+#[tokio::test]
+async fn sends_errors_to_sinks() {
+    let client = connect();
+
+    let (sink, parts) = ResponseSink::channel();
+    let call = client.divide_stream(Sum { sum: 6, num: 0 }, sink);
+    let (res, parts) = tokio::join!(call, parts.collect::<Vec<_>>());
+    assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].as_ref().unwrap_err().code(), Code::InvalidArgument);
+}
@@ -0,0 +1,164 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+
+use futures::{stream, StreamExt as _};
+use tokio::io::{duplex, AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use trapeze::raw::{self, RawBytes};
+use trapeze::{Client, ClientBuilder, RequestHandler as _, ServerConnection};
+
+pub const SERVICE: &str = "bench.Bench";
+
+// The largest payload fitting in a frame, leaving room for the request envelope
+pub const MAX_PAYLOAD_SIZE: usize = (4 << 20) - 1024;
+
+pub const PAYLOAD_SIZES: [usize; 5] = [0, 1 << 10, 64 << 10, 1 << 20, MAX_PAYLOAD_SIZE];
+
+pub const CONCURRENCY: [usize; 3] = [1, 16, 64];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    #[cfg(unix)]
+    Unix,
+    Tcp,
+    Memory,
+}
+
+pub const TRANSPORTS: &[Transport] = &[
+    #[cfg(unix)]
+    Transport::Unix,
+    Transport::Tcp,
+    Transport::Memory,
+];
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Transport::Unix => write!(f, "unix"),
+            Transport::Tcp => write!(f, "tcp"),
+            Transport::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// Formats a payload size for benchmark ids, e.g., `64KiB`.
+pub fn size_name(size: usize) -> String {
+    match size {
+        MAX_PAYLOAD_SIZE => "max".into(),
+        size if size >= 1 << 20 => format!("{}MiB", size >> 20),
+        size if size >= 1 << 10 => format!("{}KiB", size >> 10),
+        size => format!("{size}B"),
+    }
+}
+
+pub fn payload(size: usize) -> RawBytes {
+    RawBytes::new(vec![0xa5; size])
+}
+
+/// Connects a client to a server handling the benchmark methods over `transport`.
+pub async fn connect(transport: Transport, builder: ClientBuilder) -> Client {
+    match transport {
+        #[cfg(unix)]
+        Transport::Unix => {
+            let (client, server) = tokio::net::UnixStream::pair().unwrap();
+            serve(server);
+            builder.build(client)
+        }
+        Transport::Tcp => {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let (client, server) = tokio::join!(TcpStream::connect(address), listener.accept());
+            let client = client.unwrap();
+            client.set_nodelay(true).unwrap();
+            serve(server.unwrap().0);
+            builder.build(client)
+        }
+        Transport::Memory => {
+            let (client, server) = duplex(64 << 10);
+            serve(server);
+            builder.build(client)
+        }
+    }
+}
+
+fn serve(connection: impl AsyncRead + AsyncWrite + Send + 'static) {
+    let mut server = ServerConnection::new(connection);
+    server
+        .register_method(
+            "/bench.Bench/Echo",
+            raw::unary(|payload| async move { Ok(payload) }),
+        )
+        .register_method(
+            "/bench.Bench/Download",
+            raw::server_streaming(|request| {
+                let (count, size) = decode_download(&request);
+                let message = payload(size);
+                stream::repeat(message).take(count).map(Ok)
+            }),
+        )
+        .register_method(
+            "/bench.Bench/Upload",
+            raw::client_streaming(|input| async move {
+                let count = input.count().await as u64;
+                Ok(RawBytes::new(count.to_le_bytes().to_vec()))
+            }),
+        )
+        .register_method(
+            "/bench.Bench/EchoStream",
+            raw::duplex_streaming(|input| input.map(Ok)),
+        );
+    tokio::spawn(async move { server.start().await });
+}
+
+// A download request is the number of messages and their size
+fn encode_download(count: usize, size: usize) -> RawBytes {
+    let mut request = Vec::with_capacity(16);
+    request.extend_from_slice(&(count as u64).to_le_bytes());
+    request.extend_from_slice(&(size as u64).to_le_bytes());
+    RawBytes::new(request)
+}
+
+fn decode_download(request: &RawBytes) -> (usize, usize) {
+    let bytes = request.as_bytes();
+    let count = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let size = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    (count as usize, size as usize)
+}
+
+pub async fn echo(client: &Client, payload: RawBytes) {
+    let _: RawBytes = client
+        .handle_unary_request(SERVICE.into(), "Echo".into(), payload)
+        .await
+        .unwrap();
+}
+
+pub async fn download(client: &Client, count: usize, size: usize) {
+    let responses = client.handle_server_streaming_request::<_, RawBytes>(
+        SERVICE.into(),
+        "Download".into(),
+        encode_download(count, size),
+    );
+    let received = responses.map(Result::unwrap).count().await;
+    assert_eq!(received, count);
+}
+
+pub async fn upload(client: &Client, count: usize, payload: RawBytes) {
+    let input = stream::repeat(payload).take(count);
+    let _: RawBytes = client
+        .handle_client_streaming_request(SERVICE.into(), "Upload".into(), input)
+        .await
+        .unwrap();
+}
+
+pub async fn echo_stream(client: &Client, count: usize, payload: RawBytes) {
+    let input = stream::repeat(payload).take(count);
+    let responses = client.handle_duplex_streaming_request::<_, RawBytes>(
+        SERVICE.into(),
+        "EchoStream".into(),
+        input,
+    );
+    let received = responses.map(Result::unwrap).count().await;
+    assert_eq!(received, count);
+}
@@ -0,0 +1,113 @@
+//! Throughput of server streaming, client streaming and duplex streaming calls by message size,
+//! and of duplex streaming calls by number of concurrent calls.
+//!
+//! Run with `cargo bench -p trapeze --bench streaming`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use tokio::runtime::Runtime;
+use trapeze::Client;
+
+mod common;
+
+use common::{
+    connect, download, echo_stream, payload, size_name, upload, CONCURRENCY, PAYLOAD_SIZES,
+    TRANSPORTS,
+};
+
+// The number of messages sent in each call
+const MESSAGES: usize = 16;
+
+// Bytes per second, or messages per second for empty messages
+fn throughput(size: usize, messages: usize) -> Throughput {
+    match size {
+        0 => Throughput::Elements(messages as u64),
+        size => Throughput::Bytes((size * messages) as u64),
+    }
+}
+
+fn server_streaming(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("server_streaming");
+    group.sample_size(20);
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for size in PAYLOAD_SIZES {
+            group.throughput(throughput(size, MESSAGES));
+            let id = BenchmarkId::new(transport.to_string(), size_name(size));
+            group.bench_function(id, |b| {
+                b.to_async(&runtime)
+                    .iter(|| download(&client, MESSAGES, size));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn client_streaming(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("client_streaming");
+    group.sample_size(20);
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for size in PAYLOAD_SIZES {
+            let payload = payload(size);
+            group.throughput(throughput(size, MESSAGES));
+            let id = BenchmarkId::new(transport.to_string(), size_name(size));
+            group.bench_function(id, |b| {
+                b.to_async(&runtime)
+                    .iter(|| upload(&client, MESSAGES, payload.clone()));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn duplex_echo(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("duplex_echo");
+    group.sample_size(20);
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for size in PAYLOAD_SIZES {
+            let payload = payload(size);
+            group.throughput(throughput(size, MESSAGES));
+            let id = BenchmarkId::new(transport.to_string(), size_name(size));
+            group.bench_function(id, |b| {
+                b.to_async(&runtime)
+                    .iter(|| echo_stream(&client, MESSAGES, payload.clone()));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn duplex_concurrency(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let payload = payload(1 << 10);
+    let mut group = c.benchmark_group("duplex_concurrency");
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for concurrency in CONCURRENCY {
+            group.throughput(Throughput::Elements((concurrency * MESSAGES) as u64));
+            let id = BenchmarkId::new(transport.to_string(), concurrency);
+            group.bench_function(id, |b| {
+                b.to_async(&runtime).iter(|| {
+                    join_all(
+                        (0..concurrency).map(|_| echo_stream(&client, MESSAGES, payload.clone())),
+                    )
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    server_streaming,
+    client_streaming,
+    duplex_echo,
+    duplex_concurrency
+);
+criterion_main!(benches);
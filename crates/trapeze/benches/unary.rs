@@ -0,0 +1,49 @@
+//! Latency of unary calls by payload size, and their throughput by number of concurrent calls.
+//!
+//! Run with `cargo bench -p trapeze --bench unary`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use tokio::runtime::Runtime;
+use trapeze::Client;
+
+mod common;
+
+use common::{connect, echo, payload, size_name, CONCURRENCY, PAYLOAD_SIZES, TRANSPORTS};
+
+fn unary_latency(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("unary_latency");
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for size in PAYLOAD_SIZES {
+            let payload = payload(size);
+            let id = BenchmarkId::new(transport.to_string(), size_name(size));
+            group.bench_function(id, |b| {
+                b.to_async(&runtime).iter(|| echo(&client, payload.clone()));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn unary_concurrency(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let payload = payload(1 << 10);
+    let mut group = c.benchmark_group("unary_concurrency");
+    for &transport in TRANSPORTS {
+        let client = runtime.block_on(connect(transport, Client::builder()));
+        for concurrency in CONCURRENCY {
+            group.throughput(Throughput::Elements(concurrency as u64));
+            let id = BenchmarkId::new(transport.to_string(), concurrency);
+            group.bench_function(id, |b| {
+                b.to_async(&runtime)
+                    .iter(|| join_all((0..concurrency).map(|_| echo(&client, payload.clone()))));
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, unary_latency, unary_concurrency);
+criterion_main!(benches);
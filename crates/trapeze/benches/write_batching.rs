@@ -0,0 +1,42 @@
+//! Compares writing each message on its own with coalescing queued messages into larger writes,
+//! for concurrent client streaming calls sending many small messages.
+//! Each call waits for its messages to be written, so messages are only coalesced across calls.
+//!
+//! Run with `cargo bench -p trapeze --bench write_batching`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use tokio::runtime::Runtime;
+use trapeze::Client;
+
+mod common;
+
+use common::{connect, payload, upload, TRANSPORTS};
+
+const CALLS: usize = 64;
+const MESSAGES: usize = 100;
+const MESSAGE_SIZE: usize = 64;
+
+fn write_batching(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let payload = payload(MESSAGE_SIZE);
+
+    let mut group = c.benchmark_group("write_batching");
+    group.throughput(Throughput::Elements((CALLS * MESSAGES) as u64));
+    for &transport in TRANSPORTS {
+        for (mode, max_write_batch) in [("unbatched", 0), ("batched", 64 << 10)] {
+            let builder = Client::builder().max_write_batch(max_write_batch);
+            let client = runtime.block_on(connect(transport, builder));
+            let id = BenchmarkId::new(transport.to_string(), mode);
+            group.bench_function(id, |b| {
+                b.to_async(&runtime).iter(|| {
+                    join_all((0..CALLS).map(|_| upload(&client, MESSAGES, payload.clone())))
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write_batching);
+criterion_main!(benches);
@@ -0,0 +1,309 @@
+//! Load balancing of calls across several endpoints.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use trapeze::balancer::{Balancer, BalancePolicy};
+//! # async fn run() -> std::io::Result<()> {
+//! let replicas = Balancer::connect(["vsock://3:1024", "vsock://4:1024"])
+//!     .await?
+//!     .policy(BalancePolicy::LeastOutstanding)
+//!     .hedging(Duration::from_millis(50));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Generated service traits are implemented for `Balancer`, so it can be used in place of a `Client`.
+
+use std::io::Result as IoResult;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures::future::{select, Either};
+use futures::Stream;
+use tokio::pin;
+use tokio::time::sleep;
+
+use crate::client::request_handlers::{encode_payload, RequestHandler};
+use crate::context::Context;
+use crate::request::with_request_context;
+use crate::types::protos::raw_bytes::{Payload, RawBytes};
+use crate::{Client, ClientExt, Result, Status};
+
+/// How a `Balancer` chooses the endpoint for each call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BalancePolicy {
+    /// Uses each endpoint in turn.
+    #[default]
+    RoundRobin,
+
+    /// Uses the endpoint with the fewest calls in progress.
+    LeastOutstanding,
+}
+
+/// A client that spreads calls across several endpoints.
+///
+/// The metadata of the balancer is added to that of the endpoint clients,
+/// and its timeout, if set, takes precedence over theirs.
+#[derive(Clone)]
+pub struct Balancer<C = Client> {
+    endpoints: Arc<[Endpoint<C>]>,
+    next: Arc<AtomicUsize>,
+    policy: BalancePolicy,
+    hedging: Option<Duration>,
+    context: Context,
+}
+
+struct Endpoint<C> {
+    client: C,
+    outstanding: AtomicUsize,
+}
+
+impl<C> Deref for Balancer<C> {
+    type Target = Context;
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+impl<C> DerefMut for Balancer<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.context
+    }
+}
+
+impl Balancer {
+    /// Connects to each of `addresses`, using `Client::connect`.
+    ///
+    /// Addresses that fail to connect are left out of the balancer, as long as one of them connects.
+    /// Otherwise, the error of the last one is returned.
+    pub async fn connect(addresses: impl IntoIterator<Item = impl AsRef<str>>) -> IoResult<Self> {
+        let mut clients = vec![];
+        let mut error = None;
+        for address in addresses {
+            match Client::connect(address).await {
+                Ok(client) => clients.push(client),
+                Err(err) => error = Some(err),
+            }
+        }
+        match error {
+            Some(err) if clients.is_empty() => Err(err),
+            _ => Ok(Self::new(clients)),
+        }
+    }
+}
+
+impl<C> Balancer<C> {
+    /// Spreads calls across `clients`, using `BalancePolicy::RoundRobin`.
+    ///
+    /// Calls fail with `Code::Unavailable` if there are no clients.
+    pub fn new(clients: impl IntoIterator<Item = C>) -> Self {
+        let endpoints = clients
+            .into_iter()
+            .map(|client| Endpoint {
+                client,
+                outstanding: AtomicUsize::new(0),
+            })
+            .collect();
+        Self {
+            endpoints,
+            next: Arc::default(),
+            policy: BalancePolicy::default(),
+            hedging: None,
+            context: Context::default(),
+        }
+    }
+
+    /// Sets how the endpoint of each call is chosen.
+    #[must_use]
+    pub fn policy(mut self, policy: BalancePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sends a duplicate of unary calls that haven't finished after `delay` to a second endpoint.
+    /// The first successful response is used.
+    ///
+    /// Hedging only applies to unary calls, streaming calls are always sent to a single endpoint.
+    /// Only enable it for idempotent methods, as both endpoints may handle the call.
+    #[must_use]
+    pub fn hedging(mut self, delay: Duration) -> Self {
+        self.hedging = Some(delay);
+        self
+    }
+
+    /// The clients of the endpoints, in the order they were given.
+    pub fn endpoints(&self) -> impl Iterator<Item = &C> {
+        self.endpoints.iter().map(|endpoint| &endpoint.client)
+    }
+}
+
+// Counts a call as outstanding on an endpoint for as long as it's alive
+struct Outstanding<C> {
+    endpoints: Arc<[Endpoint<C>]>,
+    index: usize,
+}
+
+impl<C> Outstanding<C> {
+    fn new(endpoints: &Arc<[Endpoint<C>]>, index: usize) -> Self {
+        endpoints[index].outstanding.fetch_add(1, Ordering::Relaxed);
+        let endpoints = endpoints.clone();
+        Self { endpoints, index }
+    }
+}
+
+impl<C> Drop for Outstanding<C> {
+    fn drop(&mut self) {
+        self.endpoints[self.index]
+            .outstanding
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<C: RequestHandler + ClientExt + Send + Sync + 'static> Balancer<C> {
+    // Chooses an endpoint for a call, other than `exclude` if possible.
+    // Endpoints with a closed connection are skipped, as calls on them would fail.
+    fn pick(&self, exclude: Option<usize>) -> Result<usize> {
+        let n = self.endpoints.len();
+        if n == 0 {
+            return Err(Status::unavailable("No endpoints available"));
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % n;
+        let open = (start..n)
+            .chain(0..start)
+            .filter(|i| !self.endpoints[*i].client.is_closed());
+        let Some(first) = open.clone().next() else {
+            return Err(Status::unavailable("No open endpoints available"));
+        };
+
+        let mut candidates = open.filter(|i| Some(*i) != exclude);
+        let picked = match self.policy {
+            BalancePolicy::RoundRobin => candidates.next(),
+            BalancePolicy::LeastOutstanding => {
+                candidates.min_by_key(|i| self.endpoints[*i].outstanding.load(Ordering::Relaxed))
+            }
+        };
+        Ok(picked.unwrap_or(first))
+    }
+
+    // The client of an endpoint, with the context of the balancer
+    fn endpoint(&self, index: usize) -> (C, Outstanding<C>) {
+        let client = with_request_context(&self.endpoints[index].client, self.context.clone());
+        (client, Outstanding::new(&self.endpoints, index))
+    }
+
+    async fn unary_on<Output: Payload + 'static>(
+        &self,
+        index: usize,
+        service: String,
+        method: String,
+        payload: RawBytes,
+    ) -> Result<Output> {
+        let (client, _outstanding) = self.endpoint(index);
+        client.handle_unary_request(service, method, payload).await
+    }
+
+    async fn hedged_unary<Output: Payload + 'static>(
+        &self,
+        delay: Duration,
+        service: String,
+        method: String,
+        payload: RawBytes,
+    ) -> Result<Output> {
+        let primary = self.pick(None)?;
+        let first = self.unary_on(primary, service.clone(), method.clone(), payload.clone());
+        pin!(first);
+
+        let delay = sleep(delay);
+        pin!(delay);
+        if let Either::Left((res, _)) = select(&mut first, delay).await {
+            return res;
+        }
+
+        let secondary = self.pick(Some(primary))?;
+        if secondary == primary {
+            return first.await;
+        }
+        let second = self.unary_on(secondary, service, method, payload);
+        pin!(second);
+
+        match select(first, second).await {
+            Either::Left((Ok(output), _)) | Either::Right((Ok(output), _)) => Ok(output),
+            Either::Left((Err(_), other)) => other.await,
+            Either::Right((Err(_), other)) => other.await,
+        }
+    }
+}
+
+impl<C: RequestHandler + ClientExt + Send + Sync + 'static> RequestHandler for Balancer<C> {
+    async fn handle_unary_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> Result<Output> {
+        if let Some(delay) = self.hedging.filter(|_| self.endpoints.len() > 1) {
+            let payload = encode_payload(&payload)?;
+            return self.hedged_unary(delay, service, method, payload).await;
+        }
+
+        let (client, _outstanding) = self.endpoint(self.pick(None)?);
+        client.handle_unary_request(service, method, payload).await
+    }
+
+    fn is_closed(&self) -> bool {
+        self.endpoints
+            .iter()
+            .all(|endpoint| endpoint.client.is_closed())
+    }
+
+    fn handle_server_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        let endpoint = self.pick(None).map(|index| self.endpoint(index));
+        try_stream! {
+            let (client, _outstanding) = endpoint?;
+            let output = client.handle_server_streaming_request::<Input, Output>(service, method, payload);
+            for await value in output {
+                yield value?;
+            }
+        }
+    }
+
+    async fn handle_client_streaming_request<
+        Input: Payload + 'static,
+        Output: Payload + 'static,
+    >(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> Result<Output> {
+        let (client, _outstanding) = self.endpoint(self.pick(None)?);
+        client
+            .handle_client_streaming_request(service, method, input)
+            .await
+    }
+
+    fn handle_duplex_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        let endpoint = self.pick(None).map(|index| self.endpoint(index));
+        try_stream! {
+            let (client, _outstanding) = endpoint?;
+            let output = client.handle_duplex_streaming_request::<Input, Output>(service, method, input);
+            for await value in output {
+                yield value?;
+            }
+        }
+    }
+}
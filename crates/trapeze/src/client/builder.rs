@@ -0,0 +1,83 @@
+use std::io::Result as IoResult;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::client::keepalive::Keepalive;
+use crate::transport::connect;
+use crate::Client;
+
+/// Builds a `Client` with connection level options.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use trapeze::Client;
+/// # async fn run() -> std::io::Result<()> {
+/// let client = Client::builder()
+///     .keepalive(Duration::from_secs(10), Duration::from_secs(5))
+///     .connect("vsock://3:1024")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClientBuilder {
+    keepalive: Option<Keepalive>,
+    max_write_batch: Option<usize>,
+    max_message_size: Option<usize>,
+}
+
+impl ClientBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Probes the server every `interval` with a no-op call.
+    ///
+    /// If the server doesn't respond to a probe within `timeout`, the connection is closed,
+    /// and all calls in progress fail with `Code::Unavailable`.
+    /// Probes use a reserved method, and any response, including an error, counts as alive,
+    /// so they work with any ttrpc server.
+    #[must_use]
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some(Keepalive { interval, timeout });
+        self
+    }
+
+    /// Coalesces the messages queued while writing to the connection into writes of up to `bytes` bytes,
+    /// instead of writing each message on its own. This saves system calls when sending many small messages.
+    ///
+    /// Defaults to 64 KiB, and `0` writes each message on its own.
+    #[must_use]
+    pub fn max_write_batch(mut self, bytes: usize) -> Self {
+        self.max_write_batch = Some(bytes);
+        self
+    }
+
+    /// Sends and receives messages of up to `bytes` bytes, splitting the ones that don't fit
+    /// in a single frame (4 MiB) across several frames, and reassembling them on the other side.
+    ///
+    /// The client asks servers to split large responses in a reserved request metadata key,
+    /// so servers without support for it keep working, as long as messages fit in a frame.
+    /// Only send larger requests to servers accepting them, see `Server::max_message_size`.
+    #[must_use]
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = Some(bytes);
+        self
+    }
+
+    pub fn build<C: AsyncRead + AsyncWrite + Send + 'static>(self, connection: C) -> Client {
+        Client::with_options(
+            connection,
+            self.keepalive,
+            self.max_write_batch,
+            self.max_message_size,
+        )
+    }
+
+    pub async fn connect(self, address: impl AsRef<str>) -> IoResult<Client> {
+        let conn = connect(address).await?;
+        Ok(self.build(conn))
+    }
+}
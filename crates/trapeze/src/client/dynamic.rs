@@ -0,0 +1,307 @@
+use std::future::ready;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use async_stream::try_stream;
+use futures::{Stream, StreamExt as _};
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor, ReflectMessage as _};
+
+use crate::client::request_handlers::RequestHandler;
+use crate::context::Context;
+use crate::types::protos::raw_bytes::RawBytes;
+use crate::{Client, Result, Status};
+
+/// A client that calls methods described by a `DescriptorPool` at runtime,
+/// without the need of generated code.
+///
+/// Services are looked up by their fully qualified name (e.g., `ttrpc.test.streaming.Streaming`)
+/// and methods by their proto name (e.g., `EchoStream`).
+#[derive(Clone)]
+pub struct DynamicClient<C = Client> {
+    client: C,
+    pool: DescriptorPool,
+}
+
+impl<C: Deref<Target = Context>> Deref for DynamicClient<C> {
+    type Target = Context;
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<C: DerefMut<Target = Context>> DerefMut for DynamicClient<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+impl<C: RequestHandler + Sync> DynamicClient<C> {
+    pub fn new(client: C, pool: DescriptorPool) -> Self {
+        Self { client, pool }
+    }
+
+    pub fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    pub fn method(&self, service: &str, method: &str) -> Result<MethodDescriptor> {
+        self.pool
+            .get_service_by_name(service)
+            .and_then(|svc| svc.methods().find(|m| m.name() == method))
+            .ok_or_else(|| Status::method_not_found(service, method))
+    }
+
+    pub async fn unary(
+        &self,
+        service: &str,
+        method: &str,
+        input: DynamicMessage,
+    ) -> Result<DynamicMessage> {
+        let method = self.method(service, method)?;
+        self.unary_impl(&method, Ok(input)).await
+    }
+
+    pub fn server_streaming(
+        &self,
+        service: &str,
+        method: &str,
+        input: DynamicMessage,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + '_ {
+        let method = self.method(service, method);
+        try_stream! {
+            let method = method?;
+            for await output in self.server_streaming_impl(&method, Ok(input)) {
+                yield output?;
+            }
+        }
+    }
+
+    pub async fn client_streaming(
+        &self,
+        service: &str,
+        method: &str,
+        input: impl Stream<Item = DynamicMessage> + Send,
+    ) -> Result<DynamicMessage> {
+        let method = self.method(service, method)?;
+        self.client_streaming_impl(&method, input.map(Ok)).await
+    }
+
+    pub fn duplex_streaming<'a>(
+        &'a self,
+        service: &str,
+        method: &str,
+        input: impl Stream<Item = DynamicMessage> + Send + 'a,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + 'a {
+        let method = self.method(service, method);
+        try_stream! {
+            let method = method?;
+            for await output in self.duplex_streaming_impl(&method, input.map(Ok)) {
+                yield output?;
+            }
+        }
+    }
+
+    /// Calls a method of any kind.
+    ///
+    /// Unary and server streaming methods send the first input message, and ignore the rest of
+    /// the input, which doesn't need to finish.
+    /// Unary and client streaming methods produce exactly one output message.
+    pub fn call<'a>(
+        &'a self,
+        service: &str,
+        method: &str,
+        input: impl Stream<Item = DynamicMessage> + Send + 'a,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + 'a {
+        let method = self.method(service, method);
+        try_stream! {
+            let method = method?;
+            for await output in self.call_impl(&method, input.map(Ok)) {
+                yield output?;
+            }
+        }
+    }
+
+    /// Calls a method of any kind, using the JSON mapping of protobuf for the input and output messages.
+    pub fn call_json<'a>(
+        &'a self,
+        service: &str,
+        method: &str,
+        input: impl Stream<Item = String> + Send + 'a,
+    ) -> impl Stream<Item = Result<String>> + Send + 'a {
+        let method = self.method(service, method);
+        try_stream! {
+            let method = method?;
+            let descriptor = method.input();
+            let input = input.map(move |json| {
+                let mut deserializer = serde_json::Deserializer::from_str(&json);
+                let msg = DynamicMessage::deserialize(descriptor.clone(), &mut deserializer)
+                    .and_then(|msg| deserializer.end().map(|()| msg))
+                    .map_err(|err| Status::invalid_argument(format!("Invalid JSON input: {err}")))?;
+                Ok(msg)
+            });
+            for await output in self.call_impl(&method, input) {
+                let output = serde_json::to_string(&output?)
+                    .map_err(|err| Status::internal(format!("Error encoding JSON output: {err}")))?;
+                yield output;
+            }
+        }
+    }
+
+    fn call_impl<'a>(
+        &'a self,
+        method: &'a MethodDescriptor,
+        input: impl Stream<Item = Result<DynamicMessage>> + Send + 'a,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + 'a {
+        try_stream! {
+            match (method.is_client_streaming(), method.is_server_streaming()) {
+                (false, false) => {
+                    let input = single_input(input).await;
+                    yield self.unary_impl(method, input).await?;
+                }
+                (false, true) => {
+                    let input = single_input(input).await;
+                    for await output in self.server_streaming_impl(method, input) {
+                        yield output?;
+                    }
+                }
+                (true, false) => {
+                    yield self.client_streaming_impl(method, input).await?;
+                }
+                (true, true) => {
+                    for await output in self.duplex_streaming_impl(method, input) {
+                        yield output?;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn unary_impl(
+        &self,
+        method: &MethodDescriptor,
+        input: Result<DynamicMessage>,
+    ) -> Result<DynamicMessage> {
+        let input = encode_input(method, input)?;
+        let output = self
+            .client
+            .handle_unary_request::<RawBytes, RawBytes>(
+                method.parent_service().full_name().into(),
+                method.name().into(),
+                input,
+            )
+            .await?;
+        decode_output(method, output)
+    }
+
+    fn server_streaming_impl<'a>(
+        &'a self,
+        method: &'a MethodDescriptor,
+        input: Result<DynamicMessage>,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + 'a {
+        try_stream! {
+            let input = encode_input(method, input)?;
+            let outputs = self.client.handle_server_streaming_request::<RawBytes, RawBytes>(
+                method.parent_service().full_name().into(),
+                method.name().into(),
+                input,
+            );
+            for await output in outputs {
+                yield decode_output(method, output?)?;
+            }
+        }
+    }
+
+    async fn client_streaming_impl(
+        &self,
+        method: &MethodDescriptor,
+        input: impl Stream<Item = Result<DynamicMessage>> + Send,
+    ) -> Result<DynamicMessage> {
+        let error = Mutex::default();
+        let input = encode_input_stream(method, input, &error);
+        let output = self
+            .client
+            .handle_client_streaming_request::<RawBytes, RawBytes>(
+                method.parent_service().full_name().into(),
+                method.name().into(),
+                input,
+            )
+            .await;
+        take_error(&error)?;
+        decode_output(method, output?)
+    }
+
+    fn duplex_streaming_impl<'a>(
+        &'a self,
+        method: &'a MethodDescriptor,
+        input: impl Stream<Item = Result<DynamicMessage>> + Send + 'a,
+    ) -> impl Stream<Item = Result<DynamicMessage>> + Send + 'a {
+        try_stream! {
+            let error = Mutex::default();
+            let input = encode_input_stream(method, input, &error);
+            let outputs = self.client.handle_duplex_streaming_request::<RawBytes, RawBytes>(
+                method.parent_service().full_name().into(),
+                method.name().into(),
+                input,
+            );
+            for await output in outputs {
+                take_error(&error)?;
+                yield decode_output(method, output?)?;
+            }
+            take_error(&error)?;
+        }
+    }
+}
+
+fn encode_input(method: &MethodDescriptor, input: Result<DynamicMessage>) -> Result<RawBytes> {
+    let input = input?;
+    if input.descriptor() != method.input() {
+        return Err(Status::invalid_argument(format!(
+            "Invalid input message type: expected {}, found {}",
+            method.input().full_name(),
+            input.descriptor().full_name()
+        )));
+    }
+    Ok(RawBytes::encode(&input))
+}
+
+// Encode the messages of the input stream, stopping at the first error.
+// The error is stored in `error` so that it can be reported once the request finishes.
+fn encode_input_stream<'a>(
+    method: &'a MethodDescriptor,
+    input: impl Stream<Item = Result<DynamicMessage>> + Send + 'a,
+    error: &'a Mutex<Option<Status>>,
+) -> impl Stream<Item = RawBytes> + Send + 'a {
+    input.scan((), move |(), input| {
+        let input = match encode_input(method, input) {
+            Ok(input) => Some(input),
+            Err(status) => {
+                *error.lock().unwrap() = Some(status);
+                None
+            }
+        };
+        ready(input)
+    })
+}
+
+fn take_error(error: &Mutex<Option<Status>>) -> Result<()> {
+    match error.lock().unwrap().take() {
+        Some(status) => Err(status),
+        None => Ok(()),
+    }
+}
+
+fn decode_output(method: &MethodDescriptor, output: RawBytes) -> Result<DynamicMessage> {
+    DynamicMessage::decode(method.output(), output)
+        .map_err(|err| Status::failed_to_decode(err.into()))
+}
+
+// Takes the first message of the input, without waiting for the input to finish
+async fn single_input(input: impl Stream<Item = Result<DynamicMessage>>) -> Result<DynamicMessage> {
+    tokio::pin!(input);
+    let Some(first) = input.next().await else {
+        return Err(Status::invalid_argument(
+            "Expected an input message, found none",
+        ));
+    };
+    first
+}
@@ -0,0 +1,91 @@
+use std::future::{pending, Future};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::time::Duration;
+
+use tokio::time::{interval_at, timeout, Instant, Interval, MissedTickBehavior};
+
+use crate::context::timeout::Timeout;
+use crate::io::StreamIo;
+use crate::types::flags::Flags;
+use crate::types::frame::StreamFrame;
+use crate::types::protos::Request;
+
+// The reserved method used for keepalive probes.
+// Any response proves the peer is alive, so probes work with servers that don't implement it.
+pub(crate) const KEEPALIVE_SERVICE: &str = "trapeze.Keepalive";
+pub(crate) const KEEPALIVE_METHOD: &str = "Ping";
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+// Ticks every keepalive interval, or never if keepalive is disabled
+pub(crate) struct KeepaliveTicker {
+    interval: Option<Interval>,
+    timeout: Duration,
+}
+
+impl KeepaliveTicker {
+    pub fn new(keepalive: Option<Keepalive>) -> Self {
+        let Some(Keepalive { interval, timeout }) = keepalive else {
+            return Self {
+                interval: None,
+                timeout: Duration::ZERO,
+            };
+        };
+
+        let mut interval = interval_at(Instant::now() + interval, interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            interval: Some(interval),
+            timeout,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    pub async fn tick(&mut self) {
+        match &mut self.interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => pending().await,
+        }
+    }
+
+    // Sends a probe on `stream`, failing if no response arrives in time
+    pub fn probe(&self, mut stream: StreamIo) -> impl Future<Output = IoResult<()>> {
+        let frame = StreamFrame {
+            flags: Flags::empty(),
+            message: Request {
+                service: KEEPALIVE_SERVICE.into(),
+                method: KEEPALIVE_METHOD.into(),
+                payload: (),
+                metadata: vec![],
+                timeout_nano: Timeout::from(self.timeout).as_nanos(),
+            },
+        };
+        let sent = stream.tx.send(frame);
+        let probe_timeout = self.timeout;
+
+        async move {
+            let response = async {
+                sent.await.ok()?;
+                stream.rx.recv().await
+            };
+            if timeout(probe_timeout, response).await.is_err() {
+                log::warn!("Keepalive probe timed out, closing the connection");
+                return Err(IoError::new(
+                    ErrorKind::TimedOut,
+                    "Keepalive probe timed out",
+                ));
+            }
+            Ok(())
+        }
+    }
+}
@@ -9,17 +9,33 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot;
 use tokio::task::JoinSet;
 
+use crate::client::builder::ClientBuilder;
+use crate::client::keepalive::{Keepalive, KeepaliveTicker};
+use crate::client::retry::RetryPolicy;
+use crate::compression::{Compression, ACCEPT_ENCODING_KEY, ENCODING_KEY};
 use crate::context::metadata::Metadata;
+use crate::context::response_metadata::{ResponseMetadata, ACCEPT_RESPONSE_METADATA_KEY};
 use crate::context::timeout::Timeout;
 use crate::context::Context;
+use crate::io::fragments::MAX_MESSAGE_SIZE_KEY;
 use crate::io::{MessageIo, SendResult, StreamIo};
-use crate::transport::connect;
+use crate::server::router::Router;
+use crate::server::ServerConnection;
 use crate::types::encoding::Encodeable;
 use crate::types::frame::StreamFrame;
 use crate::types::message::Message;
+use crate::types::protos::KeyValue;
 use crate::{Result, Status};
 
+pub mod balancer;
+pub mod builder;
+#[cfg(feature = "reflect")]
+pub mod dynamic;
+pub(crate) mod keepalive;
+pub mod pool;
 pub mod request_handlers;
+pub mod retry;
+pub mod sink;
 
 type RequestFnBox = Box<dyn FnOnce(StreamIo, &mut JoinSet<IoResult<()>>) + Send>;
 
@@ -28,12 +44,24 @@ pub struct Client {
     tx: UnboundedSender<RequestFnBox>,
     _tasks: Arc<JoinSet<IoResult<()>>>,
     context: Context,
+    response_metadata: Option<ResponseMetadata>,
+    retry_policy: Option<Arc<RetryPolicy>>,
+    // The compression of the payloads sent, and the one accepted for the payloads received
+    compression: Option<Compression>,
+    accepted_compression: Option<Compression>,
+    // When set, messages are split across frames up to this size, in both directions
+    pub(crate) max_message_size: Option<usize>,
 }
 
 struct ClientInner {
     next_id: u32,
     io: MessageIo,
+    // The tasks reading from and writing to the connection
+    io_tasks: JoinSet<IoResult<()>>,
+    // The tasks of the calls in progress
     tasks: JoinSet<IoResult<()>>,
+    keepalive: KeepaliveTicker,
+    probes: JoinSet<IoResult<()>>,
 }
 
 impl Deref for Client {
@@ -50,30 +78,78 @@ impl DerefMut for Client {
 }
 
 impl ClientInner {
-    pub fn new<C: AsyncRead + AsyncWrite + Send + 'static>(connection: C) -> Self {
-        let mut tasks = JoinSet::<IoResult<()>>::new();
-        let io = MessageIo::new(&mut tasks, connection);
-        let next_id = 1;
+    pub fn new<C: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: C,
+        keepalive: Option<Keepalive>,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
+    ) -> Self {
+        let mut io_tasks = JoinSet::<IoResult<()>>::new();
+        let io = MessageIo::new(&mut io_tasks, connection, max_write_batch, max_message_size);
+        Self::with_io(io, io_tasks, 1, keepalive)
+    }
 
-        Self { next_id, io, tasks }
+    fn with_io(
+        io: MessageIo,
+        io_tasks: JoinSet<IoResult<()>>,
+        first_id: u32,
+        keepalive: Option<Keepalive>,
+    ) -> Self {
+        let keepalive = KeepaliveTicker::new(keepalive);
+
+        Self {
+            next_id: first_id,
+            io,
+            io_tasks,
+            tasks: JoinSet::new(),
+            keepalive,
+            probes: JoinSet::new(),
+        }
+    }
+
+    fn next_stream(&mut self) -> Option<StreamIo> {
+        let id = self.next_id;
+        self.next_id += 2;
+        let stream = self.io.stream(id);
+        if stream.is_none() {
+            log::error!("Ran out of stream ids");
+        }
+        stream
     }
 
     pub async fn start(&mut self, mut req_rx: UnboundedReceiver<RequestFnBox>) -> IoResult<()> {
         loop {
             tokio::select! {
+                Some(res) = self.io_tasks.join_next() => {
+                    res??;
+                },
                 Some(res) = self.tasks.join_next() => {
                     res??;
                 },
+                Some(res) = self.probes.join_next() => {
+                    if let Err(err) = res? {
+                        self.teardown(&Status::keepalive_timeout()).await;
+                        return Err(err);
+                    }
+                },
                 Some(fcn) = req_rx.recv() => {
-                    let id = self.next_id;
-                    self.next_id += 2;
-                    let Some(stream) = self.io.stream(id) else {
-                        log::error!("Ran out of stream ids");
+                    let Some(stream) = self.next_stream() else {
                         continue;
                     };
                     fcn(stream, &mut self.tasks);
                 },
-                Some((id, _)) = self.io.rx.recv() => {
+                () = self.keepalive.tick(), if self.keepalive.is_enabled() => {
+                    let Some(stream) = self.next_stream() else {
+                        continue;
+                    };
+                    self.probes.spawn(self.keepalive.probe(stream));
+                },
+                frame = self.io.rx.recv() => {
+                    let Some(frame) = frame else {
+                        // the connection is closed
+                        break;
+                    };
+                    let (id, _) = frame?;
                     log::error!("Received a message with an invalid stream id `{id}`");
                 },
                 else => {
@@ -85,15 +161,74 @@ impl ClientInner {
         }
         Ok(())
     }
+
+    // Closes the connection, and waits for the calls in progress to fail with `status`
+    async fn teardown(&mut self, status: &Status) {
+        self.io_tasks.abort_all();
+        self.io.rx.fail_streams(status);
+        while self.tasks.join_next().await.is_some() {}
+    }
 }
 
 impl Client {
     pub fn new<C: AsyncRead + AsyncWrite + Send + 'static>(connection: C) -> Self {
+        Self::with_options(connection, None, None, None)
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Uses `connection` both to make calls to the server, and to handle calls from it.
+    ///
+    /// The server must use `Server::bidirectional` for this connection.
+    /// Calls from this client use odd stream ids, and calls from the server use even ids.
+    /// The returned `ServerConnection` handles calls from the server once started,
+    /// and owns the connection: dropping it closes the connection for the client too.
+    ///
+    /// ```no_run
+    /// # use trapeze::Client;
+    /// # async fn run() -> std::io::Result<()> {
+    /// let conn = trapeze::transport::connect("unix:///tmp/agent.sock").await?;
+    /// let (client, mut server) = Client::bidirectional(conn);
+    /// // server.register(events_service);
+    /// tokio::spawn(async move { server.start().await });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bidirectional<C: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: C,
+    ) -> (Self, ServerConnection) {
+        let (server, client) =
+            ServerConnection::new_bidirectional(connection, Router::default(), 1, None, None);
+        (client, server)
+    }
+
+    pub(crate) fn with_options<C: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: C,
+        keepalive: Option<Keepalive>,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
+    ) -> Self {
+        let inner = ClientInner::new(connection, keepalive, max_write_batch, max_message_size);
+        let mut client = Self::spawn(inner);
+        client.max_message_size = max_message_size;
+        client
+    }
+
+    // Creates a client for the streams of `io` with ids of the parity of `first_id`
+    pub(crate) fn with_io(io: MessageIo, first_id: u32, max_message_size: Option<usize>) -> Self {
+        let inner = ClientInner::with_io(io, JoinSet::new(), first_id, None);
+        let mut client = Self::spawn(inner);
+        client.max_message_size = max_message_size;
+        client
+    }
+
+    fn spawn(mut inner: ClientInner) -> Self {
         let (tx, rx) = unbounded_channel();
         let mut tasks = JoinSet::<IoResult<()>>::new();
         let context = Context::default();
 
-        let mut inner = ClientInner::new(connection);
         tasks.spawn(async move { inner.start(rx).await });
 
         let tasks = Arc::new(tasks);
@@ -102,12 +237,94 @@ impl Client {
             tx,
             _tasks: tasks,
             context,
+            response_metadata: None,
+            retry_policy: None,
+            compression: None,
+            accepted_compression: None,
+            max_message_size: None,
         }
     }
 
     pub async fn connect(address: impl AsRef<str>) -> IoResult<Self> {
-        let conn = connect(address).await?;
-        Ok(Self::new(conn))
+        ClientBuilder::new().connect(address).await
+    }
+
+    /// Whether the connection of the client is closed, e.g., because the server went away.
+    /// Calls on a closed client fail with `Code::Unavailable`.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Returns a client that stores the metadata sent by the server with its responses in `sink`.
+    #[must_use]
+    pub fn with_response_metadata(&self, sink: ResponseMetadata) -> Self {
+        let mut this = self.clone();
+        this.response_metadata = Some(sink);
+        this
+    }
+
+    /// Returns a client that retries failed calls according to `policy`.
+    #[must_use]
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        let mut this = self.clone();
+        this.retry_policy = Some(Arc::new(policy));
+        this
+    }
+
+    /// Returns a client that compresses the payloads of its calls with `compression`,
+    /// and asks servers to compress the payloads of their responses with it too.
+    ///
+    /// Only use it with servers supporting `compression`, as others reject compressed payloads.
+    /// See the `compression` module for details.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[must_use]
+    pub fn with_compression(&self, compression: Compression) -> Self {
+        let mut this = self.clone();
+        this.compression = Some(compression);
+        this.accepted_compression = Some(compression);
+        this
+    }
+
+    /// Returns a client that asks servers to compress the payloads of their responses with `compression`,
+    /// while sending its own payloads uncompressed.
+    ///
+    /// Servers without support for `compression` ignore the request and respond uncompressed.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[must_use]
+    pub fn with_response_compression(&self, compression: Compression) -> Self {
+        let mut this = self.clone();
+        this.accepted_compression = Some(compression);
+        this
+    }
+
+    // The metadata to send with a request
+    fn request_metadata(&self) -> Vec<KeyValue> {
+        let mut metadata: Vec<_> = self.context.metadata.keyvalue_iter().collect();
+        if self.response_metadata.is_some() {
+            metadata.push(KeyValue {
+                key: ACCEPT_RESPONSE_METADATA_KEY.into(),
+                value: "1".into(),
+            });
+        }
+        if let Some(compression) = self.compression {
+            metadata.push(KeyValue {
+                key: ENCODING_KEY.into(),
+                value: compression.name().into(),
+            });
+        }
+        if let Some(compression) = self.accepted_compression {
+            metadata.push(KeyValue {
+                key: ACCEPT_ENCODING_KEY.into(),
+                value: compression.name().into(),
+            });
+        }
+        if let Some(max_message_size) = self.max_message_size {
+            metadata.push(KeyValue {
+                key: MAX_MESSAGE_SIZE_KEY.into(),
+                value: max_message_size.to_string(),
+            });
+        }
+        metadata
     }
 
     fn spawn_stream<Fut: Future<Output = Result<()>> + Send, Msg: Message + Encodeable>(
@@ -116,7 +333,13 @@ impl Client {
         f: impl FnOnce(SendResult, StreamIo) -> Fut + Send + 'static,
     ) -> impl Future<Output = Result<()>> + Send {
         let (tx, rx) = oneshot::channel();
-        let _ = self.tx.send(Box::new(move |stream, tasks| {
+        let (opened_tx, opened_rx) = oneshot::channel();
+        let compression = self.compression;
+        let max_message_size = self.max_message_size;
+        let _ = self.tx.send(Box::new(move |mut stream, tasks| {
+            let _ = opened_tx.send(());
+            stream.tx.set_compression(compression);
+            stream.tx.set_max_message_size(max_message_size);
             let res = stream.tx.send(frame);
             tasks.spawn(async move {
                 let _ = tx.send(f(res, stream).await);
@@ -126,12 +349,36 @@ impl Client {
 
         async move {
             let Ok(result) = rx.await else {
-                return Err(Status::channel_closed());
+                // Streams that were never opened never sent their request
+                return match opened_rx.await {
+                    Ok(()) => Err(Status::channel_closed()),
+                    Err(_) => Err(Status::connection_closed()),
+                };
             };
             result
         }
         .fuse()
     }
+
+    // Opens a new stream by sending its first frame, handing the stream over to the caller
+    pub(crate) async fn open_stream<Msg: Message + Encodeable>(
+        &self,
+        frame: impl Into<StreamFrame<Msg>> + Send + 'static,
+    ) -> Result<StreamIo> {
+        let (tx, rx) = oneshot::channel();
+        let max_message_size = self.max_message_size;
+        let _ = self.tx.send(Box::new(move |mut stream, _| {
+            stream.tx.set_max_message_size(max_message_size);
+            let res = stream.tx.send(frame);
+            let _ = tx.send((res, stream));
+        }));
+
+        let Ok((res, stream)) = rx.await else {
+            return Err(Status::connection_closed());
+        };
+        res.await.map_err(Status::send_error)?;
+        Ok(stream)
+    }
 }
 
 pub trait ClientExt: Clone + Deref<Target = Context> + DerefMut {
@@ -0,0 +1,196 @@
+use std::io::Result as IoResult;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_stream::try_stream;
+use futures::Stream;
+
+use crate::client::builder::ClientBuilder;
+use crate::client::request_handlers::RequestHandler;
+use crate::context::Context;
+use crate::request::with_request_context;
+use crate::types::protos::raw_bytes::Payload;
+use crate::{Client, Result, Status};
+
+/// A client that spreads calls across several connections to the same address.
+///
+/// Each connection has its own stream ids and writer task.
+/// Connections that are closed, e.g., because the server went away, are replaced when next used.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use trapeze::{Client, ClientPool};
+/// # async fn run() -> std::io::Result<()> {
+/// let pool = ClientPool::connect("unix:///run/agent.sock", 4).await?;
+///
+/// let builder = Client::builder().keepalive(Duration::from_secs(10), Duration::from_secs(5));
+/// let pool = ClientPool::with_builder(builder, "unix:///run/agent.sock", 4).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClientPool {
+    inner: Arc<PoolInner>,
+    context: Context,
+}
+
+struct PoolInner {
+    address: String,
+    builder: ClientBuilder,
+    slots: Vec<Mutex<Option<Client>>>,
+    next: AtomicUsize,
+}
+
+impl Deref for ClientPool {
+    type Target = Context;
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+impl DerefMut for ClientPool {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.context
+    }
+}
+
+impl ClientPool {
+    /// Opens `size` connections to `address`, using `Client::connect`.
+    pub async fn connect(address: impl Into<String>, size: usize) -> IoResult<Self> {
+        Self::with_builder(ClientBuilder::new(), address, size).await
+    }
+
+    /// Opens `size` connections to `address` with the options of `builder`.
+    ///
+    /// The same options are used to replace closed connections.
+    pub async fn with_builder(
+        builder: ClientBuilder,
+        address: impl Into<String>,
+        size: usize,
+    ) -> IoResult<Self> {
+        let address = address.into();
+        let mut slots = vec![];
+        for _ in 0..size.max(1) {
+            let client = builder.clone().connect(&address).await?;
+            slots.push(Mutex::new(Some(client)));
+        }
+
+        let inner = PoolInner {
+            address,
+            builder,
+            slots,
+            next: AtomicUsize::new(0),
+        };
+
+        Ok(Self {
+            inner: Arc::new(inner),
+            context: Context::default(),
+        })
+    }
+
+    /// The number of connections in the pool that are currently open.
+    #[must_use]
+    pub fn open_connections(&self) -> usize {
+        let slots = self.inner.slots.iter();
+        slots.filter(|slot| open_client(slot).is_some()).count()
+    }
+
+    // The client for the next call, with the context of the pool
+    async fn client(&self) -> Result<Client> {
+        let index = self.inner.next.fetch_add(1, Ordering::Relaxed) % self.inner.slots.len();
+        let slot = &self.inner.slots[index];
+
+        let client = match open_client(slot) {
+            Some(client) => client,
+            None => {
+                // Connect without holding the lock, so other calls don't wait on it.
+                // Concurrent calls may replace the same connection, the last one wins.
+                let client = self
+                    .inner
+                    .builder
+                    .clone()
+                    .connect(&self.inner.address)
+                    .await
+                    .map_err(|err| Status::unavailable(format!("Error connecting: {err}")))?;
+                *slot.lock().unwrap() = Some(client.clone());
+                client
+            }
+        };
+
+        Ok(with_request_context(&client, self.context.clone()))
+    }
+}
+
+// The client in `slot`, unless its connection is closed, in which case it's evicted
+fn open_client(slot: &Mutex<Option<Client>>) -> Option<Client> {
+    let mut slot = slot.lock().unwrap();
+    match slot.as_ref() {
+        Some(client) if !client.is_closed() => Some(client.clone()),
+        _ => {
+            *slot = None;
+            None
+        }
+    }
+}
+
+impl RequestHandler for ClientPool {
+    async fn handle_unary_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> Result<Output> {
+        self.client()
+            .await?
+            .handle_unary_request(service, method, payload)
+            .await
+    }
+
+    fn handle_server_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        let pool = self.clone();
+        try_stream! {
+            let client = pool.client().await?;
+            let output = client.handle_server_streaming_request::<Input, Output>(service, method, payload);
+            for await value in output {
+                yield value?;
+            }
+        }
+    }
+
+    async fn handle_client_streaming_request<
+        Input: Payload + 'static,
+        Output: Payload + 'static,
+    >(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> Result<Output> {
+        self.client()
+            .await?
+            .handle_client_streaming_request(service, method, input)
+            .await
+    }
+
+    fn handle_duplex_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        let pool = self.clone();
+        try_stream! {
+            let client = pool.client().await?;
+            let output = client.handle_duplex_streaming_request::<Input, Output>(service, method, input);
+            for await value in output {
+                yield value?;
+            }
+        }
+    }
+}
@@ -1,7 +1,12 @@
 use std::future::{pending, Future};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
 
 use async_stream::try_stream;
+use futures::future::Either;
 use futures::future::FusedFuture as _;
+use futures::stream::poll_fn;
 use futures::{FutureExt as _, Stream, StreamExt as _};
 use tokio::pin;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
@@ -9,54 +14,144 @@ use tokio::sync::{oneshot, RwLock};
 use tokio::time::sleep;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+use crate::client::retry::{Retrier, RetryPolicy};
+use crate::client::sink::{request_sink, RequestSink};
+use crate::compression::{compress, decompress, Compression};
+use crate::context::response_metadata::ResponseMetadata;
 use crate::context::timeout::Timeout;
 use crate::io::{StreamReceiver, StreamSender};
-use crate::types::encoding::BufExt;
+use crate::types::encoding::{BufExt, Decodeable as _};
 use crate::types::flags::Flags;
 use crate::types::frame::StreamFrame;
-use crate::types::protos::{Data, Request, Response};
-use crate::{Client, Code, Result, Status};
+use crate::types::protos::raw_bytes::{Payload, RawBytes};
+use crate::types::protos::{Data, Request, Response, Trailer};
+use crate::{Client, ClientExt as _, Code, Result, Status};
 
+/// Makes calls on behalf of generated clients.
+///
+/// Generated service traits are implemented for `Client`, `ClientPool` and `Balancer`.
 pub trait RequestHandler {
-    fn handle_unary_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn handle_unary_request<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         payload: Input,
     ) -> impl Future<Output = Result<Output>> + Send;
 
-    fn handle_server_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn handle_server_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         payload: Input,
     ) -> impl Stream<Item = Result<Output>> + Send;
 
-    fn handle_client_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn handle_client_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         input: impl Stream<Item = Input> + Send,
     ) -> impl Future<Output = Result<Output>> + Send;
 
-    fn handle_duplex_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn handle_duplex_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         input: impl Stream<Item = Input> + Send,
     ) -> impl Stream<Item = Result<Output>> + Send;
+
+    /// Whether calls are bound to fail because the connection is closed.
+    /// `Balancer` skips the endpoints that are closed.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Starts a client streaming call, returning a sink to push messages to the server,
+    /// and a future with the response.
+    ///
+    /// Unlike `handle_client_streaming_request`, messages are sent as soon as they are pushed,
+    /// and the sink reports when the call finished early, e.g., because the server failed.
+    ///
+    /// ```no_run
+    /// # use trapeze::{Client, RequestHandler as _, Result};
+    /// # async fn run(client: Client) -> Result<()> {
+    /// let (mut sink, response) = client.open_client_streaming::<String, u32>(
+    ///     "logs.Forwarder".into(),
+    ///     "Forward".into(),
+    /// );
+    /// sink.send("a line".into()).await?;
+    /// sink.close_send();
+    /// let count = response.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn open_client_streaming<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+    ) -> (
+        RequestSink<Input>,
+        impl Future<Output = Result<Output>> + Send + 'static,
+    )
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        let (sink, input, outcome) = request_sink();
+        let (output_tx, output_rx) = oneshot::channel();
+        let this = self.clone();
+        tokio::spawn(async move {
+            let res = this
+                .handle_client_streaming_request(service, method, input)
+                .await;
+            outcome.finish(res.as_ref().map(|_| ()).map_err(Clone::clone));
+            let _ = output_tx.send(res);
+        });
+
+        let output = async move {
+            let Ok(res) = output_rx.await else {
+                return Err(Status::channel_closed());
+            };
+            res
+        };
+        (sink, output)
+    }
+
+    /// Starts a duplex streaming call, returning a sink to push messages to the server,
+    /// and a stream with the responses.
+    ///
+    /// Unlike `handle_duplex_streaming_request`, messages are sent as soon as they are pushed,
+    /// even if the responses are not being consumed.
+    /// See `RequestHandler::open_client_streaming` for details.
+    fn open_duplex_streaming<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+    ) -> (
+        RequestSink<Input>,
+        impl Stream<Item = Result<Output>> + Send + 'static,
+    )
+    where
+        Self: Clone + Send + Sync + 'static,
+    {
+        let (sink, input, outcome) = request_sink();
+        let (output_tx, output_rx) = unbounded_channel();
+        let this = self.clone();
+        tokio::spawn(async move {
+            let output = this.handle_duplex_streaming_request(service, method, input);
+            pin!(output);
+            while let Some(res) = output.next().await {
+                if let Err(status) = &res {
+                    outcome.finish(Err(status.clone()));
+                }
+                if output_tx.send(res).is_err() && outcome.is_abandoned() {
+                    // nobody is sending or receiving anymore, cancel the call
+                    return;
+                }
+            }
+            outcome.finish(Ok(()));
+        });
+
+        (sink, UnboundedReceiverStream::new(output_rx))
+    }
 }
 
 macro_rules! try_join_all {
@@ -71,22 +166,23 @@ macro_rules! join_first {
     } };
 }
 
-impl RequestHandler for Client {
-    async fn handle_unary_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+// A single attempt of each kind of call, retried by the `RequestHandler` implementation
+impl Client {
+    async fn unary_attempt<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         payload: Input,
     ) -> Result<Output> {
         let (output_tx, output_rx) = oneshot::channel();
-        let metadata = self.context.metadata.keyvalue_iter().collect();
+        let metadata = self.request_metadata();
+        let sink = self.response_metadata.clone();
+        let compression = self.accepted_compression;
         let timeout = self.context.timeout;
 
+        let (flags, payload) = compress(self.compression, payload);
         let frame = StreamFrame {
-            flags: Flags::empty(),
+            flags,
             message: Request {
                 service,
                 method,
@@ -101,7 +197,7 @@ impl RequestHandler for Client {
 
             let rx = RwLock::new(&mut stream.rx);
 
-            let output = handle_server_unary(&rx, output_tx);
+            let output = handle_server_unary(&rx, output_tx, sink, compression);
             let monitor = monitor_server_stream(&rx);
             let timeout = handle_timeout(timeout);
 
@@ -121,21 +217,21 @@ impl RequestHandler for Client {
         }
     }
 
-    fn handle_server_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn server_streaming_attempt<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
         payload: Input,
     ) -> impl Stream<Item = Result<Output>> + Send {
         let (output_tx, mut output_rx) = unbounded_channel();
-        let metadata = self.context.metadata.keyvalue_iter().collect();
+        let metadata = self.request_metadata();
+        let sink = self.response_metadata.clone();
+        let compression = self.accepted_compression;
         let timeout = self.context.timeout;
 
+        let (flags, payload) = compress(self.compression, payload);
         let frame = StreamFrame {
-            flags: Flags::REMOTE_CLOSED,
+            flags: Flags::REMOTE_CLOSED | flags,
             message: Request {
                 service,
                 method,
@@ -150,7 +246,7 @@ impl RequestHandler for Client {
 
             let rx = RwLock::new(&mut stream.rx);
 
-            let output = handle_server_stream(&rx, output_tx);
+            let output = handle_server_stream(&rx, output_tx, sink, compression);
             let monitor = monitor_server_stream(&rx);
             let timeout = handle_timeout(timeout);
 
@@ -167,9 +263,11 @@ impl RequestHandler for Client {
             let fut = fut.fuse();
             pin!(fut);
             loop {
+                // yield the responses received before an error first
                 let next = tokio::select! {
-                    Err(err) = &mut fut, if !fut.is_terminated() => Err(err),
+                    biased;
                     Some(val) = output_rx.recv() => Ok(val),
+                    Err(err) = &mut fut, if !fut.is_terminated() => Err(err),
                     else => break,
                 };
                 yield next?;
@@ -177,10 +275,7 @@ impl RequestHandler for Client {
         }
     }
 
-    async fn handle_client_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    async fn client_streaming_attempt<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
@@ -188,7 +283,9 @@ impl RequestHandler for Client {
     ) -> Result<Output> {
         let (output_tx, output_rx) = oneshot::channel();
         let (input, input_fut) = handle_input_stream(input);
-        let metadata = self.context.metadata.keyvalue_iter().collect();
+        let metadata = self.request_metadata();
+        let sink = self.response_metadata.clone();
+        let compression = self.accepted_compression;
         let timeout = self.context.timeout;
 
         let frame = StreamFrame {
@@ -208,7 +305,7 @@ impl RequestHandler for Client {
             let rx = RwLock::new(&mut stream.rx);
 
             let input = handle_client_stream(&stream.tx, input);
-            let output = handle_server_unary(&rx, output_tx);
+            let output = handle_server_unary(&rx, output_tx, sink, compression);
             let monitor = monitor_server_stream(&rx);
             let timeout = handle_timeout(timeout);
 
@@ -230,10 +327,7 @@ impl RequestHandler for Client {
         }
     }
 
-    fn handle_duplex_streaming_request<
-        Input: prost::Message + Default + 'static,
-        Output: prost::Message + Default + 'static,
-    >(
+    fn duplex_streaming_attempt<Input: Payload + 'static, Output: Payload + 'static>(
         &self,
         service: String,
         method: String,
@@ -241,7 +335,9 @@ impl RequestHandler for Client {
     ) -> impl Stream<Item = Result<Output>> + Send {
         let (output_tx, mut output_rx) = unbounded_channel::<Output>();
         let (input, input_fut) = handle_input_stream(input);
-        let metadata = self.context.metadata.keyvalue_iter().collect();
+        let metadata = self.request_metadata();
+        let sink = self.response_metadata.clone();
+        let compression = self.accepted_compression;
         let timeout = self.context.timeout;
 
         let frame = StreamFrame {
@@ -261,7 +357,7 @@ impl RequestHandler for Client {
             let rx = RwLock::new(&mut stream.rx);
 
             let input = handle_client_stream(&stream.tx, input);
-            let output = handle_server_stream(&rx, output_tx);
+            let output = handle_server_stream(&rx, output_tx, sink, compression);
             let monitor = monitor_server_stream(&rx);
             let timeout = handle_timeout(timeout);
 
@@ -281,10 +377,12 @@ impl RequestHandler for Client {
             pin!(fut);
             pin!(input_fut);
             loop {
+                // yield the responses received before an error first
                 let next = tokio::select! {
+                    biased;
+                    Some(val) = output_rx.recv() => Ok(val),
                     _ = &mut input_fut, if !input_fut.is_terminated() => continue,
                     Err(err) = &mut fut, if !fut.is_terminated() => Err(err),
-                    Some(val) = output_rx.recv() => Ok(val),
                     else => break,
                 };
                 yield next?;
@@ -293,7 +391,199 @@ impl RequestHandler for Client {
     }
 }
 
-async fn handle_client_stream<Input: prost::Message + Default>(
+impl RequestHandler for Client {
+    async fn handle_unary_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> Result<Output> {
+        let policy = self.retry_policy_for(&service, &method);
+        let Some(policy) = policy.filter(|policy| policy.retries_unary()) else {
+            return self.unary_attempt(service, method, payload).await;
+        };
+
+        // encode the payload once, and send the same bytes on every attempt
+        let payload = encode_payload(&payload)?;
+        let mut retrier = Retrier::new(policy, self.context.timeout);
+        loop {
+            let attempt = self.with_timeout(retrier.timeout());
+            let res = attempt
+                .unary_attempt(service.clone(), method.clone(), payload.clone())
+                .await;
+            match res {
+                Err(status) if retrier.retry(&status).await => continue,
+                res => return res,
+            }
+        }
+    }
+
+    fn handle_server_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        payload: Input,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        // the stream shares the policy of the client, instead of copying it
+        let policy = self.retry_policy.clone();
+        let policy =
+            policy.filter(|policy| policy.for_method(&service, &method).retries_streaming());
+        let Some(policy) = policy else {
+            return Either::Left(self.server_streaming_attempt(service, method, payload));
+        };
+
+        let client = self.clone();
+        Either::Right(try_stream! {
+            let payload = encode_payload(&payload)?;
+            let policy = policy.for_method(&service, &method);
+            let mut retrier = Retrier::new(policy, client.context.timeout);
+            'attempts: loop {
+                let attempt = client.with_timeout(retrier.timeout());
+                let output = attempt.server_streaming_attempt::<RawBytes, Output>(
+                    service.clone(),
+                    method.clone(),
+                    payload.clone(),
+                );
+                pin!(output);
+                let mut received = false;
+                while let Some(res) = output.next().await {
+                    match res {
+                        Ok(val) => {
+                            received = true;
+                            yield val;
+                        }
+                        Err(status) => {
+                            if !received && retrier.retry(&status).await {
+                                continue 'attempts;
+                            }
+                            yield Err(status)?;
+                        }
+                    }
+                }
+                break;
+            }
+        })
+    }
+
+    async fn handle_client_streaming_request<
+        Input: Payload + 'static,
+        Output: Payload + 'static,
+    >(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> Result<Output> {
+        let policy = self.retry_policy_for(&service, &method);
+        let Some(policy) = policy.filter(|policy| policy.retries_streaming()) else {
+            return self.client_streaming_attempt(service, method, input).await;
+        };
+
+        let mut retrier = Retrier::new(policy, self.context.timeout);
+        let consumed = AtomicBool::new(false);
+        let input = input.fuse();
+        pin!(input);
+        loop {
+            let attempt = self.with_timeout(retrier.timeout());
+            let attempt_input = track_consumed(input.as_mut(), &consumed);
+            let res = attempt
+                .client_streaming_attempt(service.clone(), method.clone(), attempt_input)
+                .await;
+            match res {
+                Err(status)
+                    if !consumed.load(Ordering::Relaxed) && retrier.retry(&status).await =>
+                {
+                    continue
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn handle_duplex_streaming_request<Input: Payload + 'static, Output: Payload + 'static>(
+        &self,
+        service: String,
+        method: String,
+        input: impl Stream<Item = Input> + Send,
+    ) -> impl Stream<Item = Result<Output>> + Send {
+        let policy = self.retry_policy.clone();
+        let policy =
+            policy.filter(|policy| policy.for_method(&service, &method).retries_streaming());
+        let Some(policy) = policy else {
+            return Either::Left(self.duplex_streaming_attempt(service, method, input));
+        };
+
+        let client = self.clone();
+        Either::Right(try_stream! {
+            let policy = policy.for_method(&service, &method);
+            let mut retrier = Retrier::new(policy, client.context.timeout);
+            let consumed = AtomicBool::new(false);
+            let input = input.fuse();
+            pin!(input);
+            'attempts: loop {
+                let attempt = client.with_timeout(retrier.timeout());
+                let attempt_input = track_consumed(input.as_mut(), &consumed);
+                let output = attempt.duplex_streaming_attempt::<Input, Output>(
+                    service.clone(),
+                    method.clone(),
+                    attempt_input,
+                );
+                pin!(output);
+                let mut received = false;
+                while let Some(res) = output.next().await {
+                    match res {
+                        Ok(val) => {
+                            received = true;
+                            yield val;
+                        }
+                        Err(status) => {
+                            let sent = consumed.load(Ordering::Relaxed);
+                            if !received && !sent && retrier.retry(&status).await {
+                                continue 'attempts;
+                            }
+                            yield Err(status)?;
+                        }
+                    }
+                }
+                break;
+            }
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        Client::is_closed(self)
+    }
+}
+
+impl Client {
+    fn retry_policy_for(&self, service: &str, method: &str) -> Option<&RetryPolicy> {
+        let policy = self.retry_policy.as_deref()?;
+        Some(policy.for_method(service, method))
+    }
+}
+
+pub(crate) fn encode_payload(payload: &impl Payload) -> Result<RawBytes> {
+    let bytes = payload
+        .encode_to_bytes()
+        .map_err(|err| Status::invalid_argument(format!("Error encoding message: {err}")))?;
+    Ok(RawBytes::new(bytes))
+}
+
+// Records in `consumed` whether any item has been taken from `input`
+fn track_consumed<'a, T>(
+    mut input: Pin<&'a mut impl Stream<Item = T>>,
+    consumed: &'a AtomicBool,
+) -> impl Stream<Item = T> + 'a {
+    poll_fn(move |cx| {
+        let next = input.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(_)) = next {
+            consumed.store(true, Ordering::Relaxed);
+        }
+        next
+    })
+}
+
+async fn handle_client_stream<Input: Payload>(
     tx: &StreamSender,
     strm: impl Stream<Item = Input>,
 ) -> Result<()> {
@@ -314,9 +604,11 @@ async fn handle_client_stream<Input: prost::Message + Default>(
     Ok(())
 }
 
-fn handle_server_unary<'a, Output: prost::Message + Default + 'a>(
+fn handle_server_unary<'a, Output: Payload + 'a>(
     rx: &'a RwLock<&'a mut StreamReceiver>,
     tx: oneshot::Sender<Output>,
+    sink: Option<ResponseMetadata>,
+    compression: Option<Compression>,
 ) -> impl Future<Output = Result<()>> + Send + '_ {
     let mut rx = rx.try_write().unwrap();
     async move {
@@ -324,23 +616,29 @@ fn handle_server_unary<'a, Output: prost::Message + Default + 'a>(
             return Err(Status::channel_closed());
         };
         let response: Response = frame.message.decode().map_err(Status::failed_to_decode)?;
+        if let Some(sink) = sink {
+            sink.extend(&response.metadata);
+        }
         let status = response.status.unwrap_or_default();
         if status.code != Code::Ok as i32 {
             return Err(status);
         }
-        let _ = tx.send(
-            response
-                .payload
-                .decode()
-                .map_err(Status::failed_to_decode)?,
-        );
+        let payload = decompress(
+            compression,
+            frame.flags,
+            response.payload,
+            rx.max_message_size(),
+        )?;
+        let _ = tx.send(Output::decode(payload).map_err(Status::failed_to_decode)?);
         Ok(())
     }
 }
 
-fn handle_server_stream<'a, Output: prost::Message + Default + 'a>(
+fn handle_server_stream<'a, Output: Payload + 'a>(
     rx: &'a RwLock<&'a mut StreamReceiver>,
     tx: UnboundedSender<Output>,
+    sink: Option<ResponseMetadata>,
+    compression: Option<Compression>,
 ) -> impl Future<Output = Result<()>> + Send + '_ {
     let mut rx = rx.try_write().unwrap();
     async move {
@@ -350,6 +648,9 @@ fn handle_server_stream<'a, Output: prost::Message + Default + 'a>(
                     .payload
                     .ensure_empty()
                     .map_err(Status::failed_to_decode)?;
+                if let Some(sink) = &sink {
+                    sink.extend(&response.metadata);
+                }
                 let status = response.status.unwrap_or_default();
                 return Err(status);
             }
@@ -360,9 +661,17 @@ fn handle_server_stream<'a, Output: prost::Message + Default + 'a>(
                 .map_err(Status::failed_to_decode)?;
 
             if frame.flags.contains(Flags::NO_DATA) {
-                payload.ensure_empty().map_err(Status::failed_to_decode)?;
+                match &sink {
+                    // The frame closing the stream can carry the response metadata
+                    Some(sink) if frame.flags.contains(Flags::REMOTE_CLOSED) => {
+                        let trailer = Trailer::decode(payload).map_err(Status::failed_to_decode)?;
+                        sink.extend(&trailer.metadata);
+                    }
+                    _ => payload.ensure_empty().map_err(Status::failed_to_decode)?,
+                }
             } else {
-                let _ = tx.send(payload.decode().map_err(Status::failed_to_decode)?);
+                let payload = decompress(compression, frame.flags, payload, rx.max_message_size())?;
+                let _ = tx.send(Output::decode(payload).map_err(Status::failed_to_decode)?);
             }
 
             if frame.flags.contains(Flags::REMOTE_CLOSED) {
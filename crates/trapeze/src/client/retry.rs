@@ -0,0 +1,292 @@
+//! Automatic retries of failed calls.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use trapeze::retry::RetryPolicy;
+//! # use trapeze::{Client, ClientExt as _, Code};
+//! # async fn run() -> std::io::Result<()> {
+//! let policy = RetryPolicy::new()
+//!     .max_attempts(5)
+//!     .backoff(Duration::from_millis(50), Duration::from_secs(2))
+//!     .retryable_codes([Code::Unavailable, Code::ResourceExhausted])
+//!     .method("/agent.Agent/CreateContainer", RetryPolicy::disabled());
+//!
+//! let client = Client::connect("vsock://3:1024")
+//!     .await?
+//!     .with_timeout(Duration::from_secs(10))
+//!     .with_retry_policy(policy);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::context::timeout::Timeout;
+use crate::{Code, Status};
+
+/// Describes when and how a client retries failed calls.
+///
+/// Unary calls are retried whenever they fail with a retryable code.
+/// Streaming calls are only retried if enabled with `RetryPolicy::streaming`,
+/// and only as long as no message has been sent or received on the stream.
+///
+/// The timeout of the client applies to the call as a whole, including all its attempts.
+///
+/// Calls made once the connection is closed fail with `Code::Unavailable` without reaching
+/// the server, and are retried by default.
+/// Calls interrupted by the connection closing fail with `Code::Aborted` instead,
+/// since the server may have handled them, and are only retried if that code is retryable.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    retryable_codes: Vec<Code>,
+    streaming: bool,
+    // The overrides by service, and then by method, to look them up without building the path
+    methods: HashMap<String, HashMap<String, Arc<RetryPolicy>>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retryable_codes: vec![Code::Unavailable],
+            streaming: false,
+            methods: HashMap::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy making up to 3 attempts, retrying calls that fail with `Code::Unavailable`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that never retries, e.g., to override the policy of non-idempotent methods.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self::new().max_attempts(1)
+    }
+
+    /// The maximum number of attempts of a call, including the first one.
+    #[must_use]
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry, and the maximum delay between retries.
+    #[must_use]
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// The factor by which the delay grows after each retry.
+    #[must_use]
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// The fraction of the delay that is randomized, between 0 and 1.
+    #[must_use]
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The status codes on which a call is retried.
+    #[must_use]
+    pub fn retryable_codes(mut self, codes: impl IntoIterator<Item = Code>) -> Self {
+        self.retryable_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Whether streaming calls are retried, as long as no message has been sent or received.
+    #[must_use]
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Uses `policy` for calls to the method at `path`, e.g., `/ttrpc.test.streaming.Streaming/Echo`.
+    #[must_use]
+    pub fn method(mut self, path: impl AsRef<str>, policy: RetryPolicy) -> Self {
+        let path = path.as_ref();
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let (service, method) = path.split_once('/').unwrap_or((path, ""));
+        self.methods
+            .entry(service.into())
+            .or_default()
+            .insert(method.into(), Arc::new(policy));
+        self
+    }
+
+    // The policy for calls to `/{service}/{method}`
+    pub(crate) fn for_method(&self, service: &str, method: &str) -> &RetryPolicy {
+        self.methods
+            .get(service)
+            .and_then(|methods| methods.get(method))
+            .map_or(self, |policy| policy.as_ref())
+    }
+
+    pub(crate) fn retries_unary(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    pub(crate) fn retries_streaming(&self) -> bool {
+        self.streaming && self.max_attempts > 1
+    }
+
+    fn backoff_for(&self, retry: u32) -> Duration {
+        let exponent = i32::try_from(retry).unwrap_or(i32::MAX);
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let jitter = 1.0 - self.jitter * fastrand::f64();
+        Duration::from_secs_f64(backoff * jitter)
+    }
+}
+
+// Keeps track of the attempts of a single call
+pub(crate) struct Retrier<'a> {
+    policy: &'a RetryPolicy,
+    attempts: u32,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Retrier<'a> {
+    pub fn new(policy: &'a RetryPolicy, timeout: Timeout) -> Self {
+        let deadline = match timeout {
+            Timeout::Duration(t) => Instant::now().checked_add(t),
+            Timeout::None => None,
+        };
+        Self {
+            policy,
+            attempts: 1,
+            deadline,
+        }
+    }
+
+    // The timeout for the next attempt, i.e., what is left of the call's timeout
+    pub fn timeout(&self) -> Timeout {
+        match self.deadline {
+            Some(deadline) => {
+                // a zero timeout would mean no timeout at all
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                remaining.max(Duration::from_nanos(1)).into()
+            }
+            None => Timeout::None,
+        }
+    }
+
+    // Waits for the backoff delay and returns true if the call should be retried after
+    // failing with `status`, or returns false straight away otherwise
+    pub async fn retry(&mut self, status: &Status) -> bool {
+        if self.attempts >= self.policy.max_attempts {
+            return false;
+        }
+        if !self
+            .policy
+            .retryable_codes
+            .iter()
+            .any(|code| *code as i32 == status.code)
+        {
+            return false;
+        }
+
+        let backoff = self.policy.backoff_for(self.attempts - 1);
+        let resume = Instant::now() + backoff;
+        if self.deadline.is_some_and(|deadline| resume >= deadline) {
+            return false;
+        }
+
+        sleep(backoff).await;
+        self.attempts += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new()
+            .max_attempts(5)
+            .backoff(Duration::from_millis(1), Duration::from_millis(8))
+            .jitter(0.0)
+    }
+
+    #[test]
+    fn grows_backoff_up_to_max() {
+        let policy = policy();
+        let backoffs: Vec<_> = (0..6).map(|retry| policy.backoff_for(retry)).collect();
+        let millis = [1, 2, 4, 8, 8, 8].map(Duration::from_millis);
+        assert_eq!(backoffs, millis);
+    }
+
+    #[test]
+    fn randomizes_backoff_with_jitter() {
+        let policy = policy().jitter(0.5);
+        for _ in 0..100 {
+            let backoff = policy.backoff_for(3);
+            assert!(backoff > Duration::from_millis(4));
+            assert!(backoff <= Duration::from_millis(8));
+        }
+    }
+
+    #[test]
+    fn overrides_policy_per_method() {
+        let policy = policy()
+            .method("/test.Service/Create", RetryPolicy::disabled())
+            .method("test.Service/Delete", RetryPolicy::new().max_attempts(7));
+
+        assert_eq!(policy.for_method("test.Service", "Create").max_attempts, 1);
+        assert_eq!(policy.for_method("test.Service", "Delete").max_attempts, 7);
+        assert_eq!(policy.for_method("test.Service", "Get").max_attempts, 5);
+        assert_eq!(policy.for_method("test.Other", "Create").max_attempts, 5);
+    }
+
+    #[tokio::test]
+    async fn retries_retryable_codes_up_to_max_attempts() {
+        let policy = policy();
+        let mut retrier = Retrier::new(&policy, Timeout::None);
+        let status = Status::unavailable("unavailable");
+        for _ in 1..5 {
+            assert!(retrier.retry(&status).await);
+        }
+        assert!(!retrier.retry(&status).await);
+
+        let mut retrier = Retrier::new(&policy, Timeout::None);
+        assert!(!retrier.retry(&Status::aborted("aborted")).await);
+    }
+
+    #[tokio::test]
+    async fn stops_retrying_at_deadline() {
+        let policy = policy().backoff(Duration::from_secs(1), Duration::from_secs(1));
+        let mut retrier = Retrier::new(&policy, Duration::from_millis(100).into());
+
+        let start = Instant::now();
+        assert!(!retrier.retry(&Status::unavailable("unavailable")).await);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let Timeout::Duration(remaining) = retrier.timeout() else {
+            panic!("the attempts should have a timeout");
+        };
+        assert!(remaining <= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,185 @@
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+
+use futures::{ready, Sink};
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::watch;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::PollSender;
+
+use crate::{Result, Status};
+
+/// The sending half of a client streaming or duplex streaming call,
+/// see `RequestHandler::open_client_streaming` and `RequestHandler::open_duplex_streaming`.
+///
+/// Messages are sent to the server as soon as they are pushed.
+/// Up to 16 messages are buffered, sending waits while the buffer is full.
+/// Calling `close_send`, or dropping the sink, tells the server there are no more messages.
+///
+/// Once the call finishes, sending fails with the status the call failed with,
+/// or with `Code::FailedPrecondition` if the call finished successfully.
+pub struct RequestSink<Input> {
+    tx: Option<PollSender<Input>>,
+    outcome: watch::Receiver<Option<Status>>,
+}
+
+// Records how a call finished, so that its sink can report it
+pub(crate) struct CallOutcome(watch::Sender<Option<Status>>);
+
+// The number of messages buffered before `RequestSink::send` waits for them to be sent
+const BUFFER_SIZE: usize = 16;
+
+pub(crate) fn request_sink<Input: Send + 'static>(
+) -> (RequestSink<Input>, ReceiverStream<Input>, CallOutcome) {
+    let (tx, rx) = channel(BUFFER_SIZE);
+    let (outcome_tx, outcome_rx) = watch::channel(None);
+    let sink = RequestSink {
+        tx: Some(PollSender::new(tx)),
+        outcome: outcome_rx,
+    };
+    let input = ReceiverStream::new(rx);
+    (sink, input, CallOutcome(outcome_tx))
+}
+
+impl CallOutcome {
+    pub fn finish(&self, result: Result<()>) {
+        let status = result.err().unwrap_or_else(Status::call_finished);
+        self.0.send_if_modified(|outcome| {
+            if outcome.is_some() {
+                return false;
+            }
+            *outcome = Some(status);
+            true
+        });
+    }
+
+    // Whether the sink was dropped
+    pub fn is_abandoned(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+impl<Input: Send + 'static> RequestSink<Input> {
+    /// Sends a message to the server, waiting while the buffer of messages is full.
+    pub async fn send(&self, message: Input) -> Result<()> {
+        let tx = self.sender()?;
+        if tx.send(message).await.is_ok() {
+            return Ok(());
+        }
+
+        // the call finished, wait for its outcome
+        let mut outcome = self.outcome.clone();
+        let status = match outcome.wait_for(Option::is_some).await {
+            Ok(status) => status.clone(),
+            Err(_) => None,
+        };
+        Err(status.unwrap_or_else(Status::channel_closed))
+    }
+
+    /// Tells the server that there are no more messages, while still receiving its response.
+    pub fn close_send(&mut self) {
+        self.tx = None;
+    }
+
+    /// Whether the call finished, e.g., because the server responded with an error.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.outcome.borrow().is_some()
+    }
+
+    fn check_finished(&self) -> Result<()> {
+        match &*self.outcome.borrow() {
+            Some(status) => Err(status.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn sender(&self) -> Result<&Sender<Input>> {
+        self.check_finished()?;
+        self.tx
+            .as_ref()
+            .and_then(PollSender::get_ref)
+            .ok_or_else(stream_closed)
+    }
+
+    fn poll_sender(&mut self) -> Result<&mut PollSender<Input>> {
+        self.check_finished()?;
+        self.tx.as_mut().ok_or_else(stream_closed)
+    }
+
+    // The status of a call that stopped receiving messages
+    fn finished_status(&self) -> Status {
+        let status = self.outcome.borrow().clone();
+        status.unwrap_or_else(Status::call_finished)
+    }
+}
+
+fn stream_closed() -> Status {
+    Status::failed_precondition("The request stream is closed")
+}
+
+impl<Input: Send + 'static> Sink<Input> for RequestSink<Input> {
+    type Error = Status;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<()>> {
+        let res = ready!(self.poll_sender()?.poll_reserve(cx));
+        Poll::Ready(res.map_err(|_| self.finished_status()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Input) -> Result<()> {
+        let res = self.poll_sender()?.send_item(item);
+        res.map_err(|_| self.finished_status())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<Result<()>> {
+        self.close_send();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt as _, StreamExt as _};
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::Code;
+
+    #[tokio::test]
+    async fn waits_while_the_buffer_is_full() {
+        let (mut sink, mut input, _outcome) = request_sink();
+        for i in 0..BUFFER_SIZE {
+            sink.send(i).await.unwrap();
+        }
+
+        // both `send` and `poll_ready` wait for room in the buffer
+        let pending = timeout(Duration::from_millis(50), sink.send(BUFFER_SIZE)).await;
+        assert!(pending.is_err());
+        let pending = timeout(Duration::from_millis(50), sink.feed(BUFFER_SIZE)).await;
+        assert!(pending.is_err());
+
+        assert_eq!(input.next().await, Some(0));
+        timeout(Duration::from_secs(5), sink.feed(BUFFER_SIZE))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_with_the_outcome_of_the_call() {
+        let (mut sink, input, outcome) = request_sink();
+        drop(input);
+        outcome.finish(Err(Status::not_found("Missing")));
+
+        let status = sink.send(0).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        let status = sink.feed(0).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+}
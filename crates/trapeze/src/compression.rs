@@ -0,0 +1,347 @@
+//! Compression of payloads, enabled with the `gzip` and `zstd` features.
+//!
+//! Clients opt in with `Client::with_compression`. They name the compression in a reserved
+//! request metadata key, and servers compress the payloads of their responses in return.
+//! Each compressed payload is marked in the flags of its frame, so payloads can still be sent
+//! uncompressed, e.g., when compressing doesn't make them smaller.
+//! Servers only compress responses for clients asking for it, so peers without compression keep working.
+//!
+//! ```no_run
+//! # use trapeze::{Client, Compression};
+//! # #[cfg(any(feature = "gzip", feature = "zstd"))]
+//! # async fn run() -> std::io::Result<()> {
+//! let mut client = Client::connect("unix:///tmp/ttrpc-test").await?;
+//! if let Some(compression) = Compression::from_name("zstd") {
+//!     client = client.with_compression(compression);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+
+use prost::bytes::BufMut;
+use prost::encoding::{DecodeContext, WireType};
+
+use crate::context::metadata::Metadata;
+use crate::types::encoding::{Encodeable, InvalidInput};
+use crate::types::flags::Flags;
+use crate::types::protos::raw_bytes::{ProstField, RawBytes};
+use crate::{Result, Status};
+
+// Reserved request metadata key with the compression of the payloads sent by the client
+pub(crate) const ENCODING_KEY: &str = "trapeze-encoding";
+
+// Reserved request metadata key with the compressions the client accepts for the payloads of responses
+pub(crate) const ACCEPT_ENCODING_KEY: &str = "trapeze-accept-encoding";
+
+/// A compression algorithm for payloads.
+///
+/// There are no algorithms without the `gzip` or `zstd` features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// The name of the compression in the request metadata, e.g., `gzip`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Returns the compression named `name`, if it's supported.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            #[cfg(feature = "gzip")]
+            "gzip" => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn compress(self, bytes: &[u8]) -> IoResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write as _;
+
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::compress(bytes, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+
+    #[allow(unused_variables, unreachable_code)]
+    fn decompress(self, bytes: &[u8], max_size: usize) -> IoResult<Vec<u8>> {
+        let decoder: Box<dyn Read + '_> = match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(bytes)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(bytes)?),
+        };
+
+        let mut decompressed = Vec::new();
+        // stop early on decompression bombs
+        let limit = max_size as u64 + 1;
+        decoder.take(limit).read_to_end(&mut decompressed)?;
+        if decompressed.len() > max_size {
+            let msg = format!("Decompressed payload exceeds {max_size} bytes");
+            return Err(IoError::new(ErrorKind::InvalidData, msg));
+        }
+        Ok(decompressed)
+    }
+}
+
+// Takes the reserved keys out of the request metadata, returning the compression of the payloads
+// sent by the client, and the compression to use for the payloads of the responses
+pub(crate) fn negotiate(metadata: &mut Metadata) -> (Option<Compression>, Option<Compression>) {
+    let encoding = metadata.remove(ENCODING_KEY).unwrap_or_default();
+    let accepted = metadata.remove(ACCEPT_ENCODING_KEY).unwrap_or_default();
+    (first_supported(&encoding), first_supported(&accepted))
+}
+
+// The first supported compression in `values`, in order of preference
+fn first_supported(values: &[String]) -> Option<Compression> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .find_map(|name| Compression::from_name(name.trim()))
+}
+
+// A payload to send, either as is, or already encoded, e.g., because it's compressed
+#[derive(Debug)]
+pub(crate) enum OutgoingPayload<T> {
+    Message(T),
+    Encoded(RawBytes),
+}
+
+impl<T: Default> Default for OutgoingPayload<T> {
+    fn default() -> Self {
+        Self::Message(T::default())
+    }
+}
+
+// Compresses `payload` if the call uses compression, returning the flags marking it as compressed.
+// Payloads that compression doesn't make smaller are sent uncompressed.
+pub(crate) fn compress<T: Encodeable>(
+    compression: Option<Compression>,
+    payload: T,
+) -> (Flags, OutgoingPayload<T>) {
+    let Some(compression) = compression else {
+        return (Flags::empty(), OutgoingPayload::Message(payload));
+    };
+    let Ok(bytes) = payload.encode_to_bytes() else {
+        return (Flags::empty(), OutgoingPayload::Message(payload));
+    };
+    match compression.compress(&bytes) {
+        Ok(compressed) if compressed.len() < bytes.len() => {
+            let compressed = RawBytes::new(compressed);
+            (Flags::COMPRESSED, OutgoingPayload::Encoded(compressed))
+        }
+        _ => (
+            Flags::empty(),
+            OutgoingPayload::Encoded(RawBytes::new(bytes)),
+        ),
+    }
+}
+
+// Decompresses `payload` if `flags` mark it as compressed, into at most `max_size` bytes,
+// the maximum size of the messages received by the stream
+pub(crate) fn decompress(
+    compression: Option<Compression>,
+    flags: Flags,
+    payload: RawBytes,
+    max_size: usize,
+) -> Result<RawBytes> {
+    if !flags.contains(Flags::COMPRESSED) {
+        return Ok(payload);
+    }
+    let Some(compression) = compression else {
+        return Err(Status::unsupported_compression());
+    };
+    let decompressed = compression
+        .decompress(payload.as_bytes(), max_size)
+        .map_err(Status::failed_to_decompress)?;
+    Ok(RawBytes::new(decompressed))
+}
+
+impl<T: ProstField> ProstField for OutgoingPayload<T> {
+    fn encode(&self, tag: u32, buf: &mut impl BufMut) {
+        match self {
+            Self::Message(message) => ProstField::encode(message, tag, buf),
+            Self::Encoded(bytes) => ProstField::encode(bytes, tag, buf),
+        }
+    }
+    fn encoded_len(&self, tag: u32) -> usize {
+        match self {
+            Self::Message(message) => ProstField::encoded_len(message, tag),
+            Self::Encoded(bytes) => ProstField::encoded_len(bytes, tag),
+        }
+    }
+    fn clear(&mut self) {
+        match self {
+            Self::Message(message) => message.clear(),
+            Self::Encoded(bytes) => bytes.clear(),
+        }
+    }
+    fn merge(
+        &mut self,
+        wire_type: WireType,
+        buf: &mut impl prost::bytes::Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), prost::DecodeError> {
+        match self {
+            Self::Message(message) => message.merge(wire_type, buf, ctx),
+            Self::Encoded(bytes) => bytes.merge(wire_type, buf, ctx),
+        }
+    }
+}
+
+impl<T: Encodeable> Encodeable for OutgoingPayload<T> {
+    fn encode_raw(&self, buf: &mut impl BufMut) -> Result<(), InvalidInput> {
+        match self {
+            Self::Message(message) => message.encode_raw(buf),
+            Self::Encoded(bytes) => bytes.encode_raw(buf),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            Self::Message(message) => Encodeable::encoded_len(message),
+            Self::Encoded(bytes) => Encodeable::encoded_len(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::frame::MAX_DATA_LENGTH;
+    use crate::Code;
+
+    #[test]
+    fn ignores_unsupported_encodings() {
+        let mut metadata = Metadata::from([(ENCODING_KEY, "br"), (ACCEPT_ENCODING_KEY, "br, lz4")]);
+
+        assert_eq!(negotiate(&mut metadata), (None, None));
+        // the reserved keys are not visible to handlers
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn passes_uncompressed_payloads() {
+        let payload = RawBytes::new(b"payload".to_vec());
+
+        let decompressed =
+            decompress(None, Flags::empty(), payload.clone(), MAX_DATA_LENGTH).unwrap();
+        assert_eq!(decompressed.as_bytes(), payload.as_bytes());
+    }
+
+    #[test]
+    fn rejects_unexpected_compressed_payloads() {
+        // the peer never advertised an encoding
+        let payload = RawBytes::new(b"payload".to_vec());
+
+        let status = decompress(None, Flags::COMPRESSED, payload, MAX_DATA_LENGTH).unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn compressions() -> Vec<Compression> {
+        ["gzip", "zstd"]
+            .into_iter()
+            .filter_map(Compression::from_name)
+            .collect()
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn negotiates_the_first_supported_encoding() {
+        for compression in compressions() {
+            let accepted = format!("br, {}", compression.name());
+            let mut metadata = Metadata::from([(ACCEPT_ENCODING_KEY, accepted)]);
+
+            assert_eq!(negotiate(&mut metadata), (None, Some(compression)));
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn round_trips_payloads() {
+        for compression in compressions() {
+            let payload = RawBytes::new(b"payload ".repeat(100));
+
+            let (flags, compressed) = compress(Some(compression), payload.clone());
+            assert!(flags.contains(Flags::COMPRESSED));
+            let OutgoingPayload::Encoded(compressed) = compressed else {
+                panic!("the payload was not compressed");
+            };
+            assert!(compressed.as_bytes().len() < payload.as_bytes().len());
+
+            let decompressed =
+                decompress(Some(compression), flags, compressed, MAX_DATA_LENGTH).unwrap();
+            assert_eq!(decompressed.as_bytes(), payload.as_bytes());
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn sends_incompressible_payloads_uncompressed() {
+        for compression in compressions() {
+            let (flags, _) = compress(Some(compression), RawBytes::new(b"x".to_vec()));
+            assert!(flags.is_empty());
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn rejects_bad_compressed_payloads() {
+        for compression in compressions() {
+            let payload = RawBytes::new(b"not compressed".to_vec());
+
+            let status = decompress(
+                Some(compression),
+                Flags::COMPRESSED,
+                payload,
+                MAX_DATA_LENGTH,
+            )
+            .unwrap_err();
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn limits_the_decompressed_size() {
+        for compression in compressions() {
+            let payload = RawBytes::new(vec![0; 1000]);
+            let (flags, compressed) = compress(Some(compression), payload);
+            let OutgoingPayload::Encoded(compressed) = compressed else {
+                panic!("the payload was not compressed");
+            };
+
+            let status = decompress(Some(compression), flags, compressed.clone(), 999).unwrap_err();
+            assert_eq!(status.code(), Code::InvalidArgument);
+            let decompressed = decompress(Some(compression), flags, compressed, 1000).unwrap();
+            assert_eq!(decompressed.as_bytes().len(), 1000);
+        }
+    }
+}
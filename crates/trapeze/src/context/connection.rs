@@ -0,0 +1,77 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::context::extensions::Extensions;
+use crate::Client;
+
+/// Information about a connection to a server.
+///
+/// Handlers can access the connection of the current call with `get_context().connection()`.
+#[derive(Clone)]
+pub struct ConnectionInfo {
+    id: u64,
+    peer: Option<Arc<str>>,
+    extensions: Extensions,
+    client: Option<Client>,
+}
+
+impl ConnectionInfo {
+    pub(crate) fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            peer: None,
+            extensions: Extensions::default(),
+            client: None,
+        }
+    }
+
+    pub(crate) fn set_client(&mut self, client: Client) {
+        self.client = Some(client);
+    }
+
+    pub(crate) fn set_peer(&mut self, peer: Option<Arc<str>>) {
+        self.peer = peer;
+    }
+
+    /// An id that is unique to this connection within the process.
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The address of the client, if known, e.g., `tcp://127.0.0.1:50000`
+    #[must_use]
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    /// A client to make calls to the peer on the same connection.
+    /// This is only set for bidirectional connections, see `Server::bidirectional`.
+    #[must_use]
+    pub fn client(&self) -> Option<&Client> {
+        self.client.as_ref()
+    }
+
+    /// State attached to the connection, e.g., by `Server::on_connect`.
+    #[must_use]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+}
+
+impl Debug for ConnectionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionInfo")
+            .field("id", &self.id)
+            .field("peer", &self.peer)
+            .field("extensions", &self.extensions)
+            .field("bidirectional", &self.client.is_some())
+            .finish()
+    }
+}
@@ -3,13 +3,23 @@ use std::future::Future;
 use std::ops::Deref;
 use std::sync::Arc;
 
+pub mod connection;
+pub mod extensions;
 pub mod metadata;
+pub mod response_metadata;
 pub mod timeout;
 
+use connection::ConnectionInfo;
+use extensions::Extensions;
 use metadata::Metadata;
+use response_metadata::ResponseMetadata;
 use timeout::Timeout;
 use tokio::task::futures::TaskLocalFuture;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 
+use crate::compression::{self, Compression};
+use crate::io::fragments;
 use crate::ServerController;
 
 #[derive(Default, Clone, Debug)]
@@ -22,6 +32,119 @@ pub struct Context {
 pub struct ServerContext {
     pub server: ServerController,
     context: Arc<Context>,
+    service: Arc<str>,
+    method: Arc<str>,
+    pub(crate) connection: Arc<ConnectionInfo>,
+    extensions: Extensions,
+    deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    response_metadata: ResponseMetadata,
+    pub(crate) accepts_response_metadata: bool,
+    pub(crate) request_compression: Option<Compression>,
+    pub(crate) response_compression: Option<Compression>,
+    pub(crate) max_response_size: Option<usize>,
+}
+
+impl ServerContext {
+    pub(crate) fn new(
+        mut context: Context,
+        server: ServerController,
+        service: impl Into<Arc<str>>,
+        method: impl Into<Arc<str>>,
+        connection: Arc<ConnectionInfo>,
+    ) -> Self {
+        let accepts_response_metadata = context
+            .metadata
+            .remove(response_metadata::ACCEPT_RESPONSE_METADATA_KEY)
+            .is_some();
+        let (request_compression, response_compression) =
+            compression::negotiate(&mut context.metadata);
+        let max_response_size = fragments::negotiate(&mut context.metadata);
+        let deadline = match context.timeout {
+            Timeout::Duration(t) => Instant::now().checked_add(t),
+            Timeout::None => None,
+        };
+        Self {
+            server,
+            context: Arc::new(context),
+            service: service.into(),
+            method: method.into(),
+            connection,
+            extensions: Extensions::default(),
+            deadline,
+            cancellation: CancellationToken::new(),
+            response_metadata: ResponseMetadata::default(),
+            accepts_response_metadata,
+            request_compression,
+            response_compression,
+            max_response_size,
+        }
+    }
+
+    /// The fully qualified name of the called service, e.g., `ttrpc.test.streaming.Streaming`
+    #[must_use]
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The name of the called method, e.g., `EchoStream`
+    #[must_use]
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The address of the client, if known, e.g., `tcp://127.0.0.1:50000`
+    #[must_use]
+    pub fn peer(&self) -> Option<&str> {
+        self.connection.peer()
+    }
+
+    /// The connection the call arrived on, with any state attached to it.
+    #[must_use]
+    pub fn connection(&self) -> &ConnectionInfo {
+        &self.connection
+    }
+
+    /// Allows interceptors to attach state to the connection.
+    ///
+    /// Changes made by an interceptor are visible to the current call and to later calls
+    /// on the same connection, but not to calls already in progress.
+    /// Changes made elsewhere, e.g., in a handler, only affect that copy of the context.
+    pub fn connection_mut(&mut self) -> &mut ConnectionInfo {
+        Arc::make_mut(&mut self.connection)
+    }
+
+    /// State attached to the call, e.g., by an interceptor.
+    ///
+    /// State attached to the connection is available through `ServerContext::connection`.
+    #[must_use]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// The instant after which the call times out, if it has a timeout
+    #[must_use]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// A token that is cancelled once the call finishes, e.g., because it timed out
+    /// or because the server was terminated.
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// The metadata to send with the response.
+    /// It's only sent if the client accepts response metadata.
+    #[must_use]
+    pub fn response_metadata(&self) -> &ResponseMetadata {
+        &self.response_metadata
+    }
 }
 
 impl Debug for ServerContext {
@@ -63,21 +186,11 @@ pub fn try_get_server() -> Option<ServerController> {
 }
 
 pub(crate) trait WithContext: Future {
-    fn with_context(
-        self,
-        ctx: impl Into<Arc<Context>>,
-        server: ServerController,
-    ) -> TaskLocalFuture<ServerContext, Self>
+    fn with_context(self, ctx: ServerContext) -> TaskLocalFuture<ServerContext, Self>
     where
         Self: Sized,
     {
-        CONTEXT.scope(
-            ServerContext {
-                server,
-                context: ctx.into(),
-            },
-            self,
-        )
+        CONTEXT.scope(ctx, self)
     }
 }
 
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use crate::context::metadata::Metadata;
+use crate::types::protos::KeyValue;
+
+// Reserved request metadata key, used by clients to signal that they accept response metadata.
+// Servers only send response metadata when the key is present, so that peers that don't
+// support the extension never see it.
+pub(crate) const ACCEPT_RESPONSE_METADATA_KEY: &str = "trapeze-accept-response-metadata";
+
+/// Metadata sent by the server along with the response of a call.
+///
+/// On the server, handlers add metadata through `ServerContext::response_metadata`.
+/// On the client, pass a `ResponseMetadata` to `Client::with_response_metadata`,
+/// and read it once the call finishes.
+#[derive(Clone, Default, Debug)]
+pub struct ResponseMetadata(Arc<Mutex<Metadata>>);
+
+impl ResponseMetadata {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a value for `key`.
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<String>) {
+        let mut metadata = self.0.lock().unwrap();
+        metadata.entry(key.into()).or_default().push(value.into());
+    }
+
+    /// Returns the values for `key`.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Vec<String> {
+        let metadata = self.0.lock().unwrap();
+        metadata.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Returns a copy of all the metadata.
+    #[must_use]
+    pub fn metadata(&self) -> Metadata {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Removes and returns all the metadata.
+    #[must_use]
+    pub fn take(&self) -> Metadata {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    pub(crate) fn extend(&self, metadata: &[KeyValue]) {
+        for KeyValue { key, value } in metadata {
+            self.insert(key, value);
+        }
+    }
+
+    pub(crate) fn take_keyvalues(&self) -> Vec<KeyValue> {
+        self.take().into()
+    }
+}
@@ -28,14 +28,16 @@ impl<T: Send + Sync> Default for IdPool<T> {
 }
 
 impl<T: Send + Sync> IdPool<T> {
-    fn recycle(&mut self) {
+    /// Frees the ids of the dropped guards, passing each of them to `on_release`.
+    /// Ids are only freed by calling this.
+    pub fn recycle(&mut self, mut on_release: impl FnMut(u32)) {
         while let Ok(id) = self.rx.try_recv() {
             self.used.remove(&id);
+            on_release(id);
         }
     }
 
     pub fn claim(&mut self, id: u32, value: T) -> Option<IdPoolGuard> {
-        self.recycle();
         if self.used.contains_key(&id) {
             return None;
         }
@@ -47,14 +49,17 @@ impl<T: Send + Sync> IdPool<T> {
     }
 
     pub fn get(&mut self, id: u32) -> Option<&mut T> {
-        self.recycle();
         self.used.get_mut(&id)
     }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.used.values()
+    }
 }
 
 impl<T: Send + Sync> Drop for IdPool<T> {
     fn drop(&mut self) {
-        self.recycle();
+        self.recycle(|_| {});
     }
 }
 
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+use prost::bytes::BytesMut;
+
+use crate::context::metadata::Metadata;
+use crate::types::encoding::{DecodeError, TryIntoBuf as _};
+use crate::types::flags::Flags;
+use crate::types::frame::{Frame, MAX_DATA_LENGTH};
+use crate::types::message::FallibleBytesMessage;
+
+// Reserved request metadata key with the maximum size of the messages the client accepts,
+// sent by clients that accept messages split across frames
+pub(crate) const MAX_MESSAGE_SIZE_KEY: &str = "trapeze-max-message-size";
+
+// The maximum number of messages split across frames being received at once.
+// Peers write the fragments of a message one after the other, so more are a protocol violation.
+const MAX_PARTIAL_MESSAGES: usize = 16;
+
+// Takes the reserved key out of the request metadata, returning the maximum size of the messages
+// the client accepts, if it accepts messages split across frames
+pub(crate) fn negotiate(metadata: &mut Metadata) -> Option<usize> {
+    let values = metadata.remove(MAX_MESSAGE_SIZE_KEY)?;
+    values.iter().find_map(|value| value.trim().parse().ok())
+}
+
+// Whether a stream id is in use when one of its frames arrives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StreamState {
+    // The stream is open, or a request is opening it
+    Open,
+    // The stream went away, e.g., a call was cancelled while its response was being received
+    Closed,
+    Unknown,
+}
+
+// Reassembles the messages split across frames with the `FRAGMENT` flag.
+// Messages over the maximum size are discarded, and fail to decode like oversized frames.
+// Peers write the fragments of a message one after the other, so the partial messages of all
+// streams together are also limited to the maximum size.
+// Fragments for unknown streams, or too many partial messages, fail the whole connection.
+pub(crate) struct Reassembler {
+    // `None` if messages split across frames were not negotiated
+    max_message_size: Option<usize>,
+    // The total size of the partial messages
+    buffered: usize,
+    partial: HashMap<u32, Result<BytesMut, DecodeError>>,
+}
+
+impl Reassembler {
+    pub fn new(max_message_size: Option<usize>) -> Self {
+        let buffered = 0;
+        let partial = HashMap::new();
+        Self {
+            max_message_size,
+            buffered,
+            partial,
+        }
+    }
+
+    // The maximum size of the messages received, split across frames or not
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size.unwrap_or(MAX_DATA_LENGTH)
+    }
+
+    // Returns the frame with the whole message once its last fragment arrived
+    pub fn push(&mut self, frame: Frame, state: StreamState) -> IoResult<Option<Frame>> {
+        let is_fragment = frame.flags.contains(Flags::FRAGMENT);
+        let is_pending = self.partial.contains_key(&frame.id);
+        if !is_fragment && !is_pending {
+            return Ok(Some(frame));
+        }
+
+        let Frame { id, flags, message } = frame;
+        if !is_pending {
+            match state {
+                StreamState::Open => {}
+                // the rest of the message is discarded as it arrives
+                StreamState::Closed => return Ok(None),
+                StreamState::Unknown => {
+                    let msg = format!("Unexpected fragment for unknown stream {id}");
+                    return Err(IoError::new(IoErrorKind::InvalidData, msg));
+                }
+            }
+            if self.partial.len() >= MAX_PARTIAL_MESSAGES {
+                let msg = format!("Too many partial messages: > {MAX_PARTIAL_MESSAGES}");
+                return Err(IoError::new(IoErrorKind::InvalidData, msg));
+            }
+        }
+        let FallibleBytesMessage { ty, bytes } = message;
+
+        let partial = self
+            .partial
+            .entry(id)
+            .or_insert_with(|| Ok(BytesMut::new()));
+        if let Ok(buf) = partial {
+            let res = match (self.max_message_size, bytes.try_into_buf()) {
+                (None, _) => Err(invalid_input(
+                    "Unexpected fragment: messages split across frames were not negotiated".into(),
+                )),
+                (_, Err(err)) => Err(err),
+                (Some(max), Ok(bytes)) if buf.len() + bytes.len() > max => {
+                    Err(invalid_input(format!("Oversized message: > {max} bytes")))
+                }
+                (Some(max), Ok(bytes)) if self.buffered + bytes.len() > max => Err(invalid_input(
+                    format!("Partial messages exceed {max} bytes"),
+                )),
+                (Some(_), Ok(bytes)) => {
+                    buf.extend_from_slice(&bytes);
+                    self.buffered += bytes.len();
+                    Ok(())
+                }
+            };
+            if let Err(err) = res {
+                // the rest of the message is discarded as it arrives
+                self.buffered -= buf.len();
+                *partial = Err(err);
+            }
+        }
+
+        if is_fragment {
+            return Ok(None);
+        }
+
+        // the partial message was inserted above
+        let bytes = self.take(id).unwrap().map(BytesMut::freeze).into();
+        let message = FallibleBytesMessage { ty, bytes };
+        Ok(Some(Frame { id, flags, message }))
+    }
+
+    // Drops the partial message of a stream that went away
+    pub fn remove(&mut self, id: u32) {
+        self.take(id);
+    }
+
+    fn take(&mut self, id: u32) -> Option<Result<BytesMut, DecodeError>> {
+        let partial = self.partial.remove(&id)?;
+        if let Ok(buf) = &partial {
+            self.buffered -= buf.len();
+        }
+        Some(partial)
+    }
+}
+
+fn invalid_input(msg: String) -> DecodeError {
+    DecodeError::InvalidInput(msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::bytes::Bytes;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+    use crate::io::MessageReceiver;
+    use crate::types::message::MessageType;
+
+    fn frame(id: u32, flags: Flags, payload: &'static str) -> Frame {
+        let bytes = Ok(Bytes::from_static(payload.as_bytes())).into();
+        let message = FallibleBytesMessage {
+            ty: MessageType::Data,
+            bytes,
+        };
+        Frame { id, flags, message }
+    }
+
+    // Pushes a frame for an open stream
+    fn push(reassembler: &mut Reassembler, frame: Frame) -> Option<Frame> {
+        reassembler.push(frame, StreamState::Open).unwrap()
+    }
+
+    fn payload(frame: Frame) -> Result<Bytes, DecodeError> {
+        frame.message.bytes.try_into_buf()
+    }
+
+    #[test]
+    fn reassembles_fragments() {
+        let mut reassembler = Reassembler::new(Some(8));
+
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "ab")).is_none());
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "cd")).is_none());
+        let whole = push(&mut reassembler, frame(1, Flags::empty(), "ef")).unwrap();
+        assert_eq!(payload(whole).unwrap(), "abcdef");
+
+        // messages in a single frame pass through
+        let single = push(&mut reassembler, frame(1, Flags::empty(), "gh")).unwrap();
+        assert_eq!(payload(single).unwrap(), "gh");
+    }
+
+    #[test]
+    fn rejects_fragments_unless_negotiated() {
+        let mut reassembler = Reassembler::new(None);
+
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "ab")).is_none());
+        let whole = push(&mut reassembler, frame(1, Flags::empty(), "cd")).unwrap();
+        assert!(payload(whole).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let mut reassembler = Reassembler::new(Some(4));
+
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "abc")).is_none());
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "de")).is_none());
+        let whole = push(&mut reassembler, frame(1, Flags::empty(), "f")).unwrap();
+        assert!(payload(whole).is_err());
+
+        // the discarded message doesn't affect the next one
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "ab")).is_none());
+        let whole = push(&mut reassembler, frame(1, Flags::empty(), "cd")).unwrap();
+        assert_eq!(payload(whole).unwrap(), "abcd");
+    }
+
+    #[test]
+    fn limits_partial_messages_across_streams() {
+        let mut reassembler = Reassembler::new(Some(4));
+
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "abc")).is_none());
+        assert!(push(&mut reassembler, frame(3, Flags::FRAGMENT, "de")).is_none());
+
+        let second = push(&mut reassembler, frame(3, Flags::empty(), "f")).unwrap();
+        assert!(payload(second).is_err());
+        let first = push(&mut reassembler, frame(1, Flags::empty(), "d")).unwrap();
+        assert_eq!(payload(first).unwrap(), "abcd");
+    }
+
+    #[test]
+    fn drops_removed_partial_messages() {
+        let mut reassembler = Reassembler::new(Some(4));
+
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "abc")).is_none());
+        reassembler.remove(1);
+
+        assert!(push(&mut reassembler, frame(3, Flags::FRAGMENT, "ab")).is_none());
+        let whole = push(&mut reassembler, frame(3, Flags::empty(), "cd")).unwrap();
+        assert_eq!(payload(whole).unwrap(), "abcd");
+    }
+
+    #[tokio::test]
+    async fn drops_the_partial_messages_of_closed_streams() {
+        let (tx, rx) = unbounded_channel();
+        let mut receiver = MessageReceiver::from_channel(rx, Some(4));
+
+        // an abandoned partial response to an open stream
+        let stream = receiver.stream(1).unwrap();
+        tx.send(frame(1, Flags::FRAGMENT, "abc")).unwrap();
+        tx.send(frame(5, Flags::empty(), "x")).unwrap();
+        let (id, _) = receiver.recv().await.unwrap().unwrap();
+        assert_eq!(id, 5);
+        drop(stream);
+
+        let mut stream = receiver.stream(3).unwrap();
+        tx.send(frame(3, Flags::FRAGMENT, "ab")).unwrap();
+        tx.send(frame(3, Flags::empty(), "cd")).unwrap();
+        tx.send(frame(7, Flags::empty(), "x")).unwrap();
+        let (id, _) = receiver.recv().await.unwrap().unwrap();
+        assert_eq!(id, 7);
+        let frame = stream.recv().await.unwrap();
+        assert_eq!(frame.message.bytes.try_into_buf().unwrap(), "abcd");
+    }
+
+    #[test]
+    fn discards_fragments_of_closed_streams() {
+        let mut reassembler = Reassembler::new(Some(4));
+
+        let fragment = frame(1, Flags::FRAGMENT, "ab");
+        assert!(reassembler
+            .push(fragment, StreamState::Closed)
+            .unwrap()
+            .is_none());
+        // the last fragment has no partial message to complete
+        let last = reassembler
+            .push(frame(1, Flags::empty(), "cd"), StreamState::Closed)
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload(last).unwrap(), "cd");
+    }
+
+    #[test]
+    fn fails_on_fragments_for_unknown_streams() {
+        let mut reassembler = Reassembler::new(Some(4));
+
+        let fragment = frame(1, Flags::FRAGMENT, "ab");
+        let err = reassembler
+            .push(fragment, StreamState::Unknown)
+            .unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn limits_the_number_of_partial_messages() {
+        let mut reassembler = Reassembler::new(Some(4 * MAX_PARTIAL_MESSAGES));
+
+        for id in (1..).step_by(2).take(MAX_PARTIAL_MESSAGES) {
+            assert!(push(&mut reassembler, frame(id, Flags::FRAGMENT, "")).is_none());
+        }
+
+        // fragments of pending messages are still accepted
+        assert!(push(&mut reassembler, frame(1, Flags::FRAGMENT, "ab")).is_none());
+
+        let fragment = frame(1001, Flags::FRAGMENT, "ab");
+        let err = reassembler.push(fragment, StreamState::Open).unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn fails_the_connection_on_protocol_violations() {
+        let (tx, rx) = unbounded_channel();
+        let mut receiver = MessageReceiver::from_channel(rx, Some(4));
+
+        let _stream = receiver.stream(1).unwrap();
+        // a stream id the peer never used
+        tx.send(frame(3, Flags::FRAGMENT, "ab")).unwrap();
+        let err = receiver.recv().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidData);
+
+        // unless the peer opens streams with its requests
+        let (tx, rx) = unbounded_channel();
+        let mut receiver = MessageReceiver::from_channel(rx, Some(4));
+        receiver.accept_streams();
+        tx.send(frame(3, Flags::FRAGMENT, "ab")).unwrap();
+        tx.send(frame(3, Flags::empty(), "cd")).unwrap();
+        let (id, frame) = receiver.recv().await.unwrap().unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(frame.message.bytes.try_into_buf().unwrap(), "abcd");
+    }
+}
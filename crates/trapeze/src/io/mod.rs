@@ -4,7 +4,6 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context as TaskContext, Poll};
 
-use prost::bytes::Bytes;
 use thiserror::Error;
 use tokio::io::{split, AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::pin;
@@ -13,22 +12,38 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot;
 use tokio::task::JoinSet;
 
+use crate::compression::{compress, Compression};
+use crate::context::response_metadata::ResponseMetadata;
 use crate::id_pool::{IdPool, IdPoolGuard};
-use crate::types::encoding::{Decodeable as _, Encodeable, InvalidInput};
+use crate::io::fragments::{Reassembler, StreamState};
+use crate::types::encoding::{
+    ChunkedBytes, ChunkedBytesMut, Decodeable as _, Encodeable, InvalidInput,
+};
 use crate::types::flags::Flags;
 use crate::types::frame::{read_frame_bytes, Frame, StreamFrame};
-use crate::types::message::Message;
-use crate::types::protos::{Data, Response, Status};
+use crate::types::message::{FallibleBytesMessage, Message, MessageType};
+use crate::types::protos::raw_bytes::ProstField;
+use crate::types::protos::{Data, KeyValue, Response, Status, Trailer};
+
+pub(crate) mod fragments;
+
+// The default maximum size of the writes coalescing queued frames
+pub const DEFAULT_MAX_WRITE_BATCH: usize = 64 << 10;
 
 #[derive(Clone)]
 pub struct MessageSender {
-    tx: UnboundedSender<(Bytes, oneshot::Sender<()>)>,
+    tx: UnboundedSender<(ChunkedBytes, oneshot::Sender<()>)>,
 }
 
 pub struct MessageReceiver {
     // Use an unbounded_channel sender to avoid overflowing the input buffer
     rx: UnboundedReceiver<Frame>,
     streams: IdPool<UnboundedSender<StreamFrame>>,
+    // The highest stream id claimed, stream ids are used in increasing order
+    last_id: Option<u32>,
+    // Whether the peer opens streams, with the first message of a request
+    accepts_streams: bool,
+    reassembler: Reassembler,
 }
 
 pub struct MessageIo {
@@ -72,17 +87,43 @@ impl Future for SendResult {
 }
 
 impl MessageSender {
+    /// Spawns the task writing frames to `writer`.
+    ///
+    /// Frames queued while writing are coalesced into a single write of up to `max_write_batch` bytes,
+    /// `DEFAULT_MAX_WRITE_BATCH` if `None`, and each frame completes once the write including it finished.
     pub fn new(
         tasks: &mut JoinSet<IoResult<()>>,
         mut writer: impl AsyncWrite + Unpin + Send + 'static,
+        max_write_batch: Option<usize>,
     ) -> Self {
+        let max_write_batch = max_write_batch.unwrap_or(DEFAULT_MAX_WRITE_BATCH);
         let (tx, mut rx) = unbounded_channel();
         let sender = Self { tx };
         tasks.spawn(async move {
+            let mut batch = ChunkedBytesMut::default();
+            let mut completions = vec![];
             while let Some((mut bytes, ch)) = rx.recv().await {
+                if max_write_batch > 0 {
+                    batch.append(bytes);
+                    completions.push(ch);
+                    while batch.len() < max_write_batch {
+                        let Ok((bytes, ch)) = rx.try_recv() else {
+                            break;
+                        };
+                        batch.append(bytes);
+                        completions.push(ch);
+                    }
+                    bytes = batch.split();
+                } else {
+                    completions.push(ch);
+                }
+
                 // Errors writing bytes to the stream interrupt the loop
+                // Large payloads are written from their own buffers with vectored writes
                 writer.write_all_buf(&mut bytes).await?;
-                let _ = ch.send(());
+                for ch in completions.drain(..) {
+                    let _ = ch.send(());
+                }
             }
             Ok(())
         });
@@ -93,12 +134,26 @@ impl MessageSender {
         &self,
         id: u32,
         frame: impl Into<StreamFrame<Msg>>,
+    ) -> SendResult {
+        self.send_fragmented(id, frame, None)
+    }
+
+    // Like `send`, but splits messages that don't fit in a frame across several frames,
+    // for messages of up to `max_message_size` bytes, if set
+    fn send_fragmented<Msg: Message + Encodeable>(
+        &self,
+        id: u32,
+        frame: impl Into<StreamFrame<Msg>>,
+        max_message_size: Option<usize>,
     ) -> SendResult {
         // Errors encoding the message do not interrupt the loop
         let rx = (move || {
             let frame = frame.into();
             let frame = frame.into_frame(id);
-            let bytes = frame.encode_to_bytes()?;
+            let bytes = match max_message_size {
+                Some(max_message_size) => frame.encode_fragments(max_message_size)?,
+                None => frame.encode_to_chunks()?,
+            };
             let (tx, rx) = oneshot::channel();
             let _ = self.tx.send((bytes, tx));
             Ok::<_, InvalidInput>(rx)
@@ -109,75 +164,185 @@ impl MessageSender {
 
     fn stream(&self, id: u32) -> StreamSender {
         let tx = self.clone();
-        StreamSender { id, tx }
+        let response_metadata = None;
+        let compression = None;
+        let max_message_size = None;
+        StreamSender {
+            id,
+            tx,
+            response_metadata,
+            compression,
+            max_message_size,
+        }
     }
 }
 
 impl MessageReceiver {
     pub fn new(
         tasks: &mut JoinSet<IoResult<()>>,
-        mut reader: impl AsyncRead + Send + Unpin + 'static,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        max_message_size: Option<usize>,
     ) -> Self {
         let (tx, rx) = unbounded_channel();
-        let streams = IdPool::default();
-        let receiver = Self { rx, streams };
-        tasks.spawn(async move {
-            loop {
-                // Errors reading bytes from the stream interrupt the loop
-                let bytes = read_frame_bytes(&mut reader).await?;
+        tasks.spawn(read_frames(reader, move |frame| {
+            let _ = tx.send(frame);
+        }));
+        Self::from_channel(rx, max_message_size)
+    }
 
-                // This is safe because RawFrame decode errors are delayed until the
-                // message is accessed.
-                // The only possible error is if `bytes` has less than `HEADER_LENGTH`
-                // bytes, which is not possible here.
-                let frame = Frame::decode(bytes).unwrap();
+    fn from_channel(rx: UnboundedReceiver<Frame>, max_message_size: Option<usize>) -> Self {
+        let streams = IdPool::default();
+        let last_id = None;
+        let accepts_streams = false;
+        let reassembler = Reassembler::new(max_message_size);
+        Self {
+            rx,
+            streams,
+            last_id,
+            accepts_streams,
+            reassembler,
+        }
+    }
 
-                let _ = tx.send(frame);
-            }
-        });
-        receiver
+    // Frees the ids of the streams that went away, dropping their partial messages
+    fn recycle(&mut self) {
+        let reassembler = &mut self.reassembler;
+        self.streams.recycle(|id| reassembler.remove(id));
     }
 
-    pub async fn recv(&mut self) -> Option<(u32, StreamFrame)> {
+    // Fails if the peer violates the protocol, and the connection should be closed
+    pub async fn recv(&mut self) -> Option<IoResult<(u32, StreamFrame)>> {
         while let Some(frame) = self.rx.recv().await {
+            self.recycle();
+            let state = self.stream_state(frame.id);
+            let frame = match self.reassembler.push(frame, state) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            };
+
             let id = frame.id;
             let frame = frame.into_stream_frame();
 
             let Some(stream_tx) = self.streams.get(id) else {
                 // there was no stream for this id, return the message
-                return Some((id, frame));
+                return Some(Ok((id, frame)));
             };
 
             // there was a stream for this id, so attempt to send it
             if let Err(MpscSendError(frame)) = stream_tx.send(frame) {
                 // the stream was already closed, return the message and let consumers handle it
-                return Some((id, frame));
+                return Some(Ok((id, frame)));
             }
         }
         None
     }
 
+    // Accepts messages split across frames for streams that are not open yet,
+    // for receivers of the requests that open streams
+    pub fn accept_streams(&mut self) {
+        self.accepts_streams = true;
+    }
+
+    fn stream_state(&mut self, id: u32) -> StreamState {
+        if self.accepts_streams || self.streams.get(id).is_some() {
+            // requests open streams once the whole message arrived
+            StreamState::Open
+        } else if self.last_id.is_some_and(|last_id| id <= last_id) {
+            StreamState::Closed
+        } else {
+            StreamState::Unknown
+        }
+    }
+
+    // Ends the streams in progress as if the peer had failed them with `status`,
+    // e.g., when tearing down the connection
+    pub fn fail_streams(&mut self, status: &Status) {
+        self.recycle();
+        let Ok(bytes) = Response::error(status.clone()).encode_to_bytes() else {
+            return;
+        };
+        let message = FallibleBytesMessage {
+            ty: MessageType::Response,
+            bytes: Ok(bytes).into(),
+        };
+        for stream_tx in self.streams.values() {
+            let flags = Flags::empty();
+            let message = message.clone();
+            let _ = stream_tx.send(StreamFrame { flags, message });
+        }
+    }
+
     fn stream(&mut self, id: u32) -> Option<StreamReceiver> {
+        self.recycle();
         let (tx, rx) = unbounded_channel();
         let guard = self.streams.claim(id, tx)?;
+        self.last_id = self.last_id.max(Some(id));
         let guard = Arc::new(guard);
-        Some(StreamReceiver { rx, guard })
+        let max_message_size = self.reassembler.max_message_size();
+        Some(StreamReceiver {
+            rx,
+            guard,
+            max_message_size,
+        })
     }
 }
 
 impl MessageIo {
+    /// Splits `connection` into its sender and receiver.
+    ///
+    /// Messages split across frames are reassembled, for messages of up to `max_message_size` bytes.
+    /// If `None`, messages must fit in a single frame, and frames with the `FRAGMENT` flag are rejected.
     pub fn new(
         tasks: &mut JoinSet<IoResult<()>>,
         connection: impl AsyncRead + AsyncWrite + Send + 'static,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
     ) -> Self {
         let (reader, writer) = split(connection);
 
-        let rx = MessageReceiver::new(tasks, reader);
-        let tx = MessageSender::new(tasks, writer);
+        let rx = MessageReceiver::new(tasks, reader, max_message_size);
+        let tx = MessageSender::new(tasks, writer, max_write_batch);
 
         Self { tx, rx }
     }
 
+    /// Splits a connection used in both directions into the streams opened
+    /// by this end, with ids of parity `local_parity`, and the streams opened by the peer.
+    /// Both share the same writer.
+    pub fn bidirectional(
+        tasks: &mut JoinSet<IoResult<()>>,
+        connection: impl AsyncRead + AsyncWrite + Send + 'static,
+        local_parity: u32,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
+    ) -> (Self, Self) {
+        let (reader, writer) = split(connection);
+
+        let (local_tx, local_rx) = unbounded_channel();
+        let (remote_tx, remote_rx) = unbounded_channel();
+        tasks.spawn(read_frames(reader, move |frame| {
+            // frames for a side that went away are dropped
+            let tx = if frame.id % 2 == local_parity {
+                &local_tx
+            } else {
+                &remote_tx
+            };
+            let _ = tx.send(frame);
+        }));
+        let tx = MessageSender::new(tasks, writer, max_write_batch);
+
+        let local = Self {
+            tx: tx.clone(),
+            rx: MessageReceiver::from_channel(local_rx, max_message_size),
+        };
+        let remote = Self {
+            tx,
+            rx: MessageReceiver::from_channel(remote_rx, max_message_size),
+        };
+        (local, remote)
+    }
+
     pub fn stream(&mut self, id: u32) -> Option<StreamIo> {
         let rx = self.rx.stream(id)?;
         let tx = self.tx.stream(rx.id());
@@ -185,15 +350,41 @@ impl MessageIo {
     }
 }
 
+async fn read_frames(
+    mut reader: impl AsyncRead + Send + Unpin,
+    mut on_frame: impl FnMut(Frame) + Send,
+) -> IoResult<()> {
+    loop {
+        // Errors reading bytes from the stream interrupt the loop
+        let bytes = read_frame_bytes(&mut reader).await?;
+
+        // This is safe because RawFrame decode errors are delayed until the
+        // message is accessed.
+        // The only possible error is if `bytes` has less than `HEADER_LENGTH`
+        // bytes, which is not possible here.
+        let frame = Frame::decode(bytes).unwrap();
+
+        on_frame(frame);
+    }
+}
+
 #[derive(Clone)]
 pub struct StreamSender {
     id: u32,
     tx: MessageSender,
+    // When set, the metadata is sent with the response, or with the frame closing the stream
+    response_metadata: Option<ResponseMetadata>,
+    // When set, the payloads of responses and data frames are compressed
+    compression: Option<Compression>,
+    // When set, messages that don't fit in a frame are split across frames, up to this size
+    max_message_size: Option<usize>,
 }
 
 pub struct StreamReceiver {
     rx: UnboundedReceiver<StreamFrame>,
     guard: Arc<IdPoolGuard>,
+    // The maximum size of the messages received, also once decompressed
+    max_message_size: usize,
 }
 
 pub struct StreamIo {
@@ -210,27 +401,61 @@ impl StreamSender {
         &self,
         frame: impl Into<StreamFrame<Msg>>,
     ) -> SendResult {
-        self.tx.send(self.id, frame)
+        self.tx
+            .send_fragmented(self.id, frame, self.max_message_size)
+    }
+
+    pub fn set_response_metadata(&mut self, response_metadata: ResponseMetadata) {
+        self.response_metadata = Some(response_metadata);
+    }
+
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: Option<usize>) {
+        self.max_message_size = max_message_size;
+    }
+
+    fn take_response_metadata(&self) -> Option<Vec<KeyValue>> {
+        let response_metadata = self.response_metadata.as_ref()?;
+        Some(response_metadata.take_keyvalues())
     }
 
     pub fn error(&self, status: Status) -> SendResult {
-        self.send(Response::error(status))
+        let metadata = self.take_response_metadata().unwrap_or_default();
+        self.send(Response::error(status).with_metadata(metadata))
     }
 
-    pub fn respond<Payload: prost::Message + Default>(&self, payload: Payload) -> SendResult {
-        self.send(Response::ok(payload))
+    pub fn respond<Payload: ProstField + Encodeable + Default>(
+        &self,
+        payload: Payload,
+    ) -> SendResult {
+        let metadata = self.take_response_metadata().unwrap_or_default();
+        let (flags, payload) = compress(self.compression, payload);
+        self.send(StreamFrame {
+            flags,
+            message: Response::ok(payload).with_metadata(metadata),
+        })
     }
 
-    pub fn data<Payload: prost::Message + Default>(&self, payload: Payload) -> SendResult {
+    pub fn data<Payload: Encodeable>(&self, payload: Payload) -> SendResult {
+        let (flags, payload) = compress(self.compression, payload);
         self.send(StreamFrame {
-            flags: Flags::empty(),
+            flags,
             message: Data { payload },
         })
     }
 
     pub fn close_data(&self) -> SendResult {
+        let flags = Flags::REMOTE_CLOSED | Flags::NO_DATA;
+        if let Some(metadata) = self.take_response_metadata() {
+            let payload = Trailer { metadata };
+            let message = Data { payload };
+            return self.send(StreamFrame { flags, message });
+        }
         self.send(StreamFrame {
-            flags: Flags::REMOTE_CLOSED | Flags::NO_DATA,
+            flags,
             message: Data { payload: () },
         })
     }
@@ -245,6 +470,10 @@ impl StreamReceiver {
         self.guard.clone()
     }
 
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
     pub async fn recv(&mut self) -> Option<StreamFrame> {
         self.rx.recv().await
     }
@@ -259,3 +488,66 @@ impl StreamIo {
         (self.tx, self.rx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Records the length of every write
+    #[derive(Clone, Default)]
+    struct Writes(Arc<Mutex<Vec<usize>>>);
+
+    impl AsyncWrite for Writes {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut TaskContext<'_>,
+            buf: &[u8],
+        ) -> Poll<IoResult<usize>> {
+            self.0.lock().unwrap().push(buf.len());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<IoResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<IoResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    // Queues `count` frames before the writer task runs, and returns the length of every write
+    async fn write_frames(count: u32, max_write_batch: Option<usize>) -> Vec<usize> {
+        let mut tasks = JoinSet::new();
+        let writes = Writes::default();
+        let tx = MessageSender::new(&mut tasks, writes.clone(), max_write_batch);
+        let sent: Vec<_> = (0..count)
+            .map(|id| tx.send(id * 2 + 1, Status::cancelled("Stop")))
+            .collect();
+        for res in sent {
+            res.await.unwrap();
+        }
+        let writes = writes.0.lock().unwrap().clone();
+        writes
+    }
+
+    #[tokio::test]
+    async fn coalesces_queued_frames() {
+        let single = write_frames(4, Some(0)).await;
+        assert_eq!(single.len(), 4);
+
+        let batched = write_frames(4, None).await;
+        assert_eq!(batched, [single.iter().sum::<usize>()]);
+    }
+
+    #[tokio::test]
+    async fn limits_the_size_of_batches() {
+        let single = write_frames(4, Some(0)).await;
+
+        // batches stop growing once they reach the limit
+        let batched = write_frames(4, Some(single[0] + 1)).await;
+        assert_eq!(batched, [single[0] + single[1], single[2] + single[3]]);
+    }
+}
@@ -1,7 +1,10 @@
 mod client;
+pub mod compression;
 mod context;
 mod id_pool;
 mod io;
+pub mod proxy;
+mod request;
 mod server;
 mod service;
 pub mod transport;
@@ -9,22 +12,40 @@ mod types;
 
 pub type Result<T, E = Status> = std::result::Result<T, E>;
 
-pub use client::{Client, ClientExt};
+pub use client::builder::ClientBuilder;
+pub use client::pool::ClientPool;
+pub use client::request_handlers::RequestHandler;
+pub use client::sink::RequestSink;
+pub use client::{balancer, retry, Client, ClientExt};
+pub use compression::Compression;
+pub use context::connection::ConnectionInfo;
+pub use context::extensions::Extensions;
 pub use context::metadata::Metadata;
+pub use context::response_metadata::ResponseMetadata;
 pub use context::timeout::Timeout;
-pub use context::{get_context, get_server, try_get_context, try_get_server, Context};
-pub use server::{Server, ServerConnection, ServerController, ServerHandle};
+pub use context::{
+    get_context, get_server, try_get_context, try_get_server, Context, ServerContext,
+};
+pub use request::Request;
+pub use server::raw;
+pub use server::sink::ResponseSink;
+pub use server::{ExcessConnections, Server, ServerConnection, ServerController, ServerHandle};
 pub use trapeze_macros::*;
+pub use types::protos::error_details;
+pub use types::protos::raw_bytes::RawBytes;
 pub use types::protos::status::StatusExt;
 pub use types::protos::{Code, Status};
 
 #[doc(hidden)]
 pub mod __codegen_prelude {
     pub use crate::client::request_handlers::RequestHandler;
+    pub use crate::request::{server_request, with_request_context};
     pub use crate::server::method_handlers::MethodHandler;
+    pub use crate::server::sink::{forward_to_sink, sink_stream};
     pub use crate::service::{
         ClientStreamingMethod, DuplexStreamingMethod, ServerStreamingMethod, Service, UnaryMethod,
     };
+    pub use crate::types::protos::raw_bytes::Payload;
 }
 
 #[doc(hidden)]
@@ -44,3 +65,10 @@ pub mod stream {
 pub mod codegen {
     pub use trapeze_codegen::*;
 }
+
+#[cfg(feature = "reflect")]
+pub mod reflect {
+    pub use prost_reflect::*;
+
+    pub use crate::client::dynamic::DynamicClient;
+}
@@ -0,0 +1,197 @@
+//! A service that forwards calls to upstream servers.
+//!
+//! Calls are forwarded frame by frame, without decoding their payloads, so any kind of
+//! method (unary or streaming) of any service can be proxied.
+//! The metadata and timeout of the incoming call are preserved.
+//! Compressed payloads from the client are decompressed before being forwarded.
+//!
+//! ```no_run
+//! # use trapeze::proxy::Proxy;
+//! # use trapeze::{Client, ClientExt as _, Server};
+//! # async fn run() -> std::io::Result<()> {
+//! let agent = Client::connect("vsock://3:1024").await?;
+//! let health = Client::connect("unix:///run/health.sock").await?;
+//!
+//! let proxy = Proxy::new()
+//!     .route("grpc.Health", health)
+//!     .route("", agent.with_metadata([("forwarded-by", "host-proxy")]));
+//!
+//! Server::new().register_proxy(proxy).bind("unix:///run/agent.sock").await?.await
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::compression::{decompress, Compression};
+use crate::context::get_context;
+use crate::context::response_metadata::ACCEPT_RESPONSE_METADATA_KEY;
+use crate::context::timeout::Timeout;
+use crate::io::fragments::MAX_MESSAGE_SIZE_KEY;
+use crate::io::{StreamIo, StreamReceiver, StreamSender};
+use crate::server::method_handlers::MethodHandler;
+use crate::server::raw::RawMethod;
+use crate::types::flags::Flags;
+use crate::types::frame::StreamFrame;
+use crate::types::protos::raw_bytes::RawBytes;
+use crate::types::protos::{Data, Request, Response};
+use crate::{Client, Result, Status};
+
+/// Forwards calls to upstream clients, routing them by service name.
+///
+/// Register it on a server with `Server::register_proxy`.
+#[derive(Clone, Default)]
+pub struct Proxy {
+    routes: Vec<(String, Client)>,
+}
+
+impl Proxy {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forwards calls to the methods of `service`, e.g., `grpc.Health`, to `upstream`.
+    ///
+    /// An empty service name forwards every call to a service without a route of its own.
+    /// It takes the place of the server's fallback, see `Server::fallback`,
+    /// so whichever of the two is registered last handles those calls.
+    ///
+    /// The metadata of `upstream` is added to the metadata of forwarded calls,
+    /// and its timeout is used for calls that don't specify one.
+    #[must_use]
+    pub fn route(mut self, service: impl Into<String>, upstream: Client) -> Self {
+        self.routes.push((service.into(), upstream));
+        self
+    }
+
+    // The paths of the routes, matching every method of their service, and their methods
+    pub(crate) fn into_methods(self) -> impl Iterator<Item = (String, RawMethod)> {
+        self.routes.into_iter().map(|(service, upstream)| {
+            let path = if service.is_empty() {
+                "*".to_string()
+            } else {
+                format!("/{service}/*")
+            };
+            (path, forward(upstream))
+        })
+    }
+}
+
+/// Returns a raw method that forwards calls to `upstream`.
+///
+/// This can be used with `Server::register_method` to forward arbitrary paths.
+#[must_use]
+pub fn forward(upstream: Client) -> RawMethod {
+    RawMethod(Arc::new(ForwardMethod { upstream }))
+}
+
+struct ForwardMethod {
+    upstream: Client,
+}
+
+#[async_trait]
+impl MethodHandler for ForwardMethod {
+    async fn handle(&self, flags: Flags, payload: RawBytes, mut stream: StreamIo) -> Result<()> {
+        let ctx = get_context();
+
+        let mut metadata = ctx.metadata.clone();
+        for (key, values) in self.upstream.metadata.iter() {
+            metadata
+                .entry(key.clone())
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+
+        // Let the upstream server know that the client accepts response metadata,
+        // it will be forwarded with the rest of the response
+        if ctx.accepts_response_metadata {
+            metadata.insert(ACCEPT_RESPONSE_METADATA_KEY.into(), vec!["1".into()]);
+        }
+
+        // Large responses can only be split across frames if both the upstream client and
+        // the client of the call accept them, up to the smallest of their sizes
+        if let (Some(upstream), Some(client)) =
+            (self.upstream.max_message_size, ctx.max_response_size)
+        {
+            let max_message_size = upstream.min(client);
+            metadata.insert(
+                MAX_MESSAGE_SIZE_KEY.into(),
+                vec![max_message_size.to_string()],
+            );
+        }
+
+        let timeout = match ctx.timeout {
+            Timeout::None => self.upstream.timeout,
+            timeout => timeout,
+        };
+
+        let frame = StreamFrame {
+            flags,
+            message: Request {
+                service: ctx.service().into(),
+                method: ctx.method().into(),
+                payload,
+                metadata: metadata.into(),
+                timeout_nano: timeout.as_nanos(),
+            },
+        };
+
+        let mut upstream = self.upstream.open_stream(frame).await?;
+
+        let compression = ctx.request_compression;
+        let requests = forward_requests(&mut stream.rx, &upstream.tx, compression);
+        let responses = forward_responses(&mut upstream.rx, &stream.tx);
+
+        // The call is over once the upstream server finishes it
+        tokio::select! {
+            res = responses => res,
+            Err(err) = requests => Err(err),
+        }
+    }
+}
+
+async fn forward_requests(
+    rx: &mut StreamReceiver,
+    tx: &StreamSender,
+    compression: Option<Compression>,
+) -> Result<()> {
+    while let Some(frame) = rx.recv().await {
+        let Data { payload } = frame.message.decode().map_err(Status::failed_to_decode)?;
+        let payload = decompress(compression, frame.flags, payload, rx.max_message_size())?;
+        let message = Data { payload };
+        let flags = frame.flags.difference(Flags::COMPRESSED);
+        tx.send(StreamFrame { flags, message })
+            .await
+            .map_err(Status::send_error)?;
+
+        if flags.contains(Flags::REMOTE_CLOSED) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn forward_responses(rx: &mut StreamReceiver, tx: &StreamSender) -> Result<()> {
+    while let Some(frame) = rx.recv().await {
+        let flags = frame.flags;
+
+        if let Ok(message) = frame.message.decode::<Response>() {
+            tx.send(StreamFrame { flags, message })
+                .await
+                .map_err(Status::send_error)?;
+            return Ok(());
+        }
+
+        let message: Data = frame.message.decode().map_err(Status::failed_to_decode)?;
+        tx.send(StreamFrame { flags, message })
+            .await
+            .map_err(Status::send_error)?;
+
+        if flags.contains(Flags::REMOTE_CLOSED) {
+            return Ok(());
+        }
+    }
+    Err(Status::channel_closed())
+}
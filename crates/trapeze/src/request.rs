@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::context::extensions::Extensions;
+use crate::context::metadata::Metadata;
+use crate::context::response_metadata::ResponseMetadata;
+use crate::context::timeout::Timeout;
+use crate::context::{get_context, Context};
+use crate::ClientExt;
+
+/// A request message along with the context of the call.
+///
+/// This is used by services generated with `Config::request_wrappers`.
+/// On the server, it carries the context of the incoming call, so that handlers
+/// don't need to rely on `get_context()`.
+/// On the client, its metadata and timeout are used for the call, in addition to those of the client.
+#[derive(Debug)]
+pub struct Request<T> {
+    message: T,
+    context: Context,
+    peer: Option<Arc<str>>,
+    extensions: Extensions,
+    deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    response_metadata: ResponseMetadata,
+}
+
+impl<T> Request<T> {
+    pub fn new(message: T) -> Self {
+        Self {
+            message,
+            context: Context::default(),
+            peer: None,
+            extensions: Extensions::default(),
+            deadline: None,
+            cancellation: CancellationToken::new(),
+            response_metadata: ResponseMetadata::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: impl Into<Metadata>) -> Self {
+        self.context.metadata = metadata.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: impl Into<Timeout>) -> Self {
+        self.context.timeout = timeout.into();
+        self
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.message
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.message
+    }
+
+    pub fn into_inner(self) -> T {
+        self.message
+    }
+
+    pub fn into_parts(self) -> (Context, T) {
+        (self.context, self.message)
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.context.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.context.metadata
+    }
+
+    pub fn timeout(&self) -> Timeout {
+        self.context.timeout
+    }
+
+    /// The instant after which the call times out, if it has a timeout.
+    /// This is only set for requests received by a server.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// The address of the client, if known, e.g., `tcp://127.0.0.1:50000`.
+    /// This is only set for requests received by a server.
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    /// State attached to the call by the server's interceptors.
+    /// This is only set for requests received by a server.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// A token that is cancelled once the call finishes on the server,
+    /// e.g., because it timed out or because the server was terminated.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// The metadata to send with the response.
+    /// This is only used for requests received by a server.
+    pub fn response_metadata(&self) -> &ResponseMetadata {
+        &self.response_metadata
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Request<U> {
+        Request {
+            message: f(self.message),
+            context: self.context,
+            peer: self.peer,
+            extensions: self.extensions,
+            deadline: self.deadline,
+            cancellation: self.cancellation,
+            response_metadata: self.response_metadata,
+        }
+    }
+}
+
+impl<T> From<T> for Request<T> {
+    fn from(message: T) -> Self {
+        Self::new(message)
+    }
+}
+
+// Wraps a message received by a server with the context of the current call
+#[doc(hidden)]
+pub fn server_request<T>(message: T) -> Request<T> {
+    let ctx = get_context();
+    Request {
+        message,
+        context: Context::clone(&ctx),
+        peer: ctx.peer().map(Into::into),
+        extensions: ctx.extensions().clone(),
+        deadline: ctx.deadline(),
+        cancellation: ctx.cancellation_token(),
+        response_metadata: ctx.response_metadata().clone(),
+    }
+}
+
+// Returns a client that sends the metadata and timeout of `context` in addition to its own
+#[doc(hidden)]
+pub fn with_request_context<C: ClientExt>(client: &C, context: Context) -> C {
+    let Context { metadata, timeout } = context;
+    let mut client = client.clone();
+    for (key, values) in metadata.iter() {
+        client
+            .metadata
+            .entry(key.clone())
+            .or_default()
+            .extend(values.iter().cloned());
+    }
+    if let Timeout::Duration(_) = timeout {
+        client.timeout = timeout;
+    }
+    client
+}
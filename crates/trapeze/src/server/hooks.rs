@@ -0,0 +1,69 @@
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::sync::Arc;
+
+use crate::context::connection::ConnectionInfo;
+use crate::context::ServerContext;
+use crate::types::protos::Status;
+use crate::Result;
+
+type AcceptErrorHook = dyn Fn(&IoError) + Send + Sync;
+type ConnectHook = dyn Fn(&mut ConnectionInfo) + Send + Sync;
+type DisconnectHook = dyn Fn(&ConnectionInfo, Option<&IoError>) + Send + Sync;
+type StreamOpenHook = dyn Fn(&ServerContext) + Send + Sync;
+type StreamCloseHook = dyn Fn(&ServerContext, Option<&Status>) + Send + Sync;
+type Interceptor = dyn Fn(&mut ServerContext) -> Result<()> + Send + Sync;
+
+// Callbacks on the lifecycle of connections and streams, and interceptors of calls
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub on_accept_error: Option<Arc<AcceptErrorHook>>,
+    pub on_connect: Option<Arc<ConnectHook>>,
+    pub on_disconnect: Option<Arc<DisconnectHook>>,
+    pub on_stream_open: Option<Arc<StreamOpenHook>>,
+    pub on_stream_close: Option<Arc<StreamCloseHook>>,
+    pub interceptors: Vec<Arc<Interceptor>>,
+}
+
+impl Hooks {
+    pub fn accept_error(&self, err: &IoError) {
+        if let Some(hook) = &self.on_accept_error {
+            hook(err);
+        }
+    }
+
+    pub fn connect(&self, info: &mut ConnectionInfo) {
+        if let Some(hook) = &self.on_connect {
+            hook(info);
+        }
+    }
+
+    pub fn disconnect(&self, info: &ConnectionInfo, result: &IoResult<()>) {
+        let Some(hook) = &self.on_disconnect else {
+            return;
+        };
+        // the client closing the connection is a clean disconnect
+        match result {
+            Err(err) if err.kind() != ErrorKind::UnexpectedEof => hook(info, Some(err)),
+            _ => hook(info, None),
+        }
+    }
+
+    // Runs the interceptors in order, stopping at the first one that rejects the call
+    pub fn intercept(&self, ctx: &mut ServerContext) -> Result<()> {
+        self.interceptors
+            .iter()
+            .try_for_each(|interceptor| interceptor(ctx))
+    }
+
+    pub fn stream_open(&self, ctx: &ServerContext) {
+        if let Some(hook) = &self.on_stream_open {
+            hook(ctx);
+        }
+    }
+
+    pub fn stream_close(&self, ctx: &ServerContext, status: Option<&Status>) {
+        if let Some(hook) = &self.on_stream_close {
+            hook(ctx, status);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use tokio::sync::RwLock;
 use tokio::time::sleep;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+use crate::compression::decompress;
 use crate::context::get_context;
 use crate::context::timeout::Timeout;
 use crate::io::{StreamIo, StreamReceiver, StreamSender};
@@ -16,13 +17,13 @@ use crate::service::{
 };
 use crate::types::encoding::BufExt;
 use crate::types::flags::Flags;
-use crate::types::protos::raw_bytes::RawBytes;
+use crate::types::protos::raw_bytes::{Payload, RawBytes};
 use crate::types::protos::{Data, Status};
 use crate::Result;
 
 #[async_trait]
 pub trait MethodHandler {
-    async fn handle(&self, flags: Flags, payload: RawBytes, stream: &mut StreamIo) -> Result<()>;
+    async fn handle(&self, flags: Flags, payload: RawBytes, stream: StreamIo) -> Result<()>;
 }
 
 macro_rules! try_join_all {
@@ -39,13 +40,13 @@ macro_rules! join_first {
 
 #[async_trait]
 impl<
-        Input: prost::Message + Default,
-        Output: prost::Message + Default,
+        Input: Payload,
+        Output: Payload,
         FutOut: Future<Output = Result<Output>> + Send,
         F: Fn(Input) -> FutOut + Send + Sync,
     > MethodHandler for UnaryMethod<Input, Output, F>
 {
-    async fn handle(&self, flags: Flags, payload: RawBytes, stream: &mut StreamIo) -> Result<()> {
+    async fn handle(&self, flags: Flags, payload: RawBytes, mut stream: StreamIo) -> Result<()> {
         if !flags.is_empty() {
             // Unary methos should have empty flags
             return Err(Status::invalid_request_flags(Flags::empty(), flags));
@@ -53,7 +54,7 @@ impl<
 
         let rx = RwLock::new(&mut stream.rx);
 
-        let payload: Input = payload.decode().map_err(Status::failed_to_decode)?;
+        let payload = Input::decode(payload).map_err(Status::failed_to_decode)?;
 
         let fut = (self.method)(payload);
 
@@ -73,13 +74,13 @@ impl<
 
 #[async_trait]
 impl<
-        Input: prost::Message + Default,
-        Output: prost::Message + Default,
+        Input: Payload,
+        Output: Payload,
         StrmOut: Stream<Item = Result<Output>> + Send,
         F: Fn(Input) -> StrmOut + Send + Sync,
     > MethodHandler for ServerStreamingMethod<Input, Output, F>
 {
-    async fn handle(&self, flags: Flags, payload: RawBytes, stream: &mut StreamIo) -> Result<()> {
+    async fn handle(&self, flags: Flags, payload: RawBytes, mut stream: StreamIo) -> Result<()> {
         let rx = RwLock::new(&mut stream.rx);
 
         if flags.bits() != Flags::REMOTE_CLOSED.bits() {
@@ -88,7 +89,7 @@ impl<
             return Err(Status::invalid_request_flags(Flags::REMOTE_CLOSED, flags));
         }
 
-        let payload: Input = payload.decode().map_err(Status::failed_to_decode)?;
+        let payload = Input::decode(payload).map_err(Status::failed_to_decode)?;
 
         let output_strm = (self.method)(payload);
 
@@ -108,13 +109,13 @@ impl<
 
 #[async_trait]
 impl<
-        Input: prost::Message + Default,
-        Output: prost::Message + Default,
+        Input: Payload,
+        Output: Payload,
         FutOut: Future<Output = Result<Output>> + Send,
         F: Fn(UnboundedReceiverStream<Input>) -> FutOut + Send + Sync,
     > MethodHandler for ClientStreamingMethod<Input, Output, F>
 {
-    async fn handle(&self, flags: Flags, payload: RawBytes, stream: &mut StreamIo) -> Result<()> {
+    async fn handle(&self, flags: Flags, payload: RawBytes, mut stream: StreamIo) -> Result<()> {
         let rx = RwLock::new(&mut stream.rx);
 
         if flags.bits() != (Flags::REMOTE_OPEN | Flags::NO_DATA).bits() {
@@ -150,13 +151,13 @@ impl<
 
 #[async_trait]
 impl<
-        Input: prost::Message + Default,
-        Output: prost::Message + Default,
+        Input: Payload,
+        Output: Payload,
         StrmOut: Stream<Item = Result<Output>> + Send,
         F: Fn(UnboundedReceiverStream<Input>) -> StrmOut + Send + Sync,
     > MethodHandler for DuplexStreamingMethod<Input, Output, F>
 {
-    async fn handle(&self, flags: Flags, payload: RawBytes, stream: &mut StreamIo) -> Result<()> {
+    async fn handle(&self, flags: Flags, payload: RawBytes, mut stream: StreamIo) -> Result<()> {
         let rx = RwLock::new(&mut stream.rx);
 
         if flags.bits() != (Flags::REMOTE_OPEN | Flags::NO_DATA).bits() {
@@ -196,12 +197,13 @@ fn make_input_stream<Input>() -> (UnboundedSender<Input>, UnboundedReceiverStrea
     (tx, strm)
 }
 
-fn handle_client_stream<'a, Input: prost::Message + Default + 'a>(
+fn handle_client_stream<'a, Input: Payload + 'a>(
     rx: &'a RwLock<&'a mut StreamReceiver>,
     tx: UnboundedSender<Input>,
 ) -> impl Future<Output = Result<()>> + Send + '_ {
     // lock the mutex synchronously to avoid other handlers getting a lock before us
     let mut rx = rx.try_write().unwrap();
+    let compression = get_context().request_compression;
     async move {
         while let Some(frame) = rx.recv().await {
             let Data { payload } = frame
@@ -212,7 +214,8 @@ fn handle_client_stream<'a, Input: prost::Message + Default + 'a>(
             if frame.flags.contains(Flags::NO_DATA) {
                 payload.ensure_empty().map_err(Status::failed_to_decode)?;
             } else {
-                let _ = tx.send(payload.decode().map_err(Status::failed_to_decode)?);
+                let payload = decompress(compression, frame.flags, payload, rx.max_message_size())?;
+                let _ = tx.send(Input::decode(payload).map_err(Status::failed_to_decode)?);
             }
 
             if frame.flags.contains(Flags::REMOTE_CLOSED) {
@@ -232,7 +235,7 @@ async fn monitor_client_stream(rx: &RwLock<&mut StreamReceiver>) -> Result<()> {
     Ok(())
 }
 
-async fn handle_server_stream<Output: prost::Message + Default>(
+async fn handle_server_stream<Output: Payload>(
     tx: &StreamSender,
     strm: impl Stream<Item = Result<Output>>,
 ) -> Result<()> {
@@ -247,7 +250,7 @@ async fn handle_server_stream<Output: prost::Message + Default>(
     Ok(())
 }
 
-async fn handle_server_unary<Output: prost::Message + Default>(
+async fn handle_server_unary<Output: Payload>(
     tx: &StreamSender,
     fut: impl Future<Output = Result<Output>>,
 ) -> Result<()> {
@@ -256,7 +259,7 @@ async fn handle_server_unary<Output: prost::Message + Default>(
     Ok(())
 }
 
-async fn handle_timeout() -> Result<()> {
+pub(crate) async fn handle_timeout() -> Result<()> {
     let t = get_context().timeout;
     match t {
         Timeout::Duration(t) => sleep(t).await,
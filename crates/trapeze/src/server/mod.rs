@@ -1,31 +1,71 @@
-use std::collections::HashMap;
-use std::io::{ErrorKind, Result as IoResult};
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::pin_mut;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::task::JoinSet;
+use tokio::time::{sleep, Instant};
 
+use crate::client::keepalive::{KEEPALIVE_METHOD, KEEPALIVE_SERVICE};
+use crate::client::Client;
+use crate::compression::decompress;
+use crate::context::connection::ConnectionInfo;
 use crate::context::timeout::Timeout;
-use crate::context::{Context, WithContext};
+use crate::context::{Context, ServerContext, WithContext};
 use crate::io::MessageIo;
-use crate::server::method_handlers::MethodHandler;
+use crate::proxy::Proxy;
+use crate::server::raw::{RawCall, RawMethod};
+use crate::server::router::Router;
 use crate::service::Service;
 use crate::transport::{bind, Listener};
+use crate::types::flags::Flags;
 use crate::types::frame::StreamFrame;
 use crate::types::protos::{Request, Status};
+use crate::Result;
 
 pub mod controller;
 pub mod handle;
+mod hooks;
 pub mod method_handlers;
+pub mod raw;
+pub(crate) mod router;
+pub mod sink;
 
 pub use controller::ServerController;
 pub use handle::ServerHandle;
+use hooks::Hooks;
 
 #[derive(Default)]
 pub struct Server {
-    methods: HashMap<&'static str, Arc<dyn MethodHandler + Send + Sync>>,
+    methods: Router,
     tasks: JoinSet<IoResult<()>>,
+    limits: ConnectionLimits,
+    max_connections: Option<usize>,
+    excess_connections: ExcessConnections,
+    bidirectional: bool,
+    max_write_batch: Option<usize>,
+    max_message_size: Option<usize>,
+    hooks: Hooks,
+}
+
+/// What a `Server` does with new connections once it reached its maximum number of connections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExcessConnections {
+    /// Accepts and closes new connections straight away.
+    #[default]
+    Refuse,
+
+    /// Stops accepting connections, leaving them in the listener's backlog until other connections close.
+    Wait,
+}
+
+// Limits on the lifetime of each connection
+#[derive(Clone, Copy, Default)]
+struct ConnectionLimits {
+    max_idle_time: Option<Duration>,
+    max_connection_age: Option<Duration>,
 }
 
 impl Server {
@@ -41,30 +81,264 @@ impl Server {
         self
     }
 
+    /// Registers a raw method handler for `path`.
+    ///
+    /// The path is either the full path of a method (e.g., `/ttrpc.test.streaming.Streaming/Echo`),
+    /// or a prefix followed by `*` (e.g., `/ttrpc.test.streaming.Streaming/*` or `*`).
+    /// Full paths take precedence over prefixes, and longer prefixes over shorter ones.
+    #[must_use]
+    pub fn register_method(mut self, path: impl Into<String>, method: RawMethod) -> Self {
+        self.methods.insert(path, method.0);
+        self
+    }
+
+    /// Registers the routes of `proxy`, forwarding calls to their upstream clients.
+    #[must_use]
+    pub fn register_proxy(mut self, proxy: Proxy) -> Self {
+        self.methods
+            .extend(proxy.into_methods().map(|(path, method)| (path, method.0)));
+        self
+    }
+
+    /// Registers a handler for calls to methods that are not otherwise registered.
+    /// See `raw::any` for details.
+    ///
+    /// The fallback is registered for the `*` path, like a proxy route with an empty service name,
+    /// so the last of them to be registered handles those calls.
+    #[must_use]
+    pub fn fallback<F, Fut>(self, method: F) -> Self
+    where
+        F: Fn(RawCall) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        self.register_method("*", raw::any(method))
+    }
+
+    /// Closes connections that have had no calls in progress for `max_idle_time`.
+    #[must_use]
+    pub fn max_idle_time(mut self, max_idle_time: Duration) -> Self {
+        self.limits.max_idle_time = Some(max_idle_time);
+        self
+    }
+
+    /// Closes connections once they are `max_connection_age` old.
+    ///
+    /// Calls in progress are allowed to finish, while new calls are rejected
+    /// with `Code::Unavailable`, so that clients can retry them on a new connection.
+    #[must_use]
+    pub fn max_connection_age(mut self, max_connection_age: Duration) -> Self {
+        self.limits.max_connection_age = Some(max_connection_age);
+        self
+    }
+
+    /// Limits the number of open connections.
+    /// Excess connections are refused by default, see `Server::excess_connections`.
+    #[must_use]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    #[must_use]
+    pub fn excess_connections(mut self, excess_connections: ExcessConnections) -> Self {
+        self.excess_connections = excess_connections;
+        self
+    }
+
+    /// Allows handling calls from clients, and making calls to them, on the same connection.
+    ///
+    /// Clients must use `Client::bidirectional` to connect.
+    /// Handlers can make calls to the client with `get_context().connection().client()`.
+    /// Calls from clients use odd stream ids, and calls to them use even ids.
+    #[must_use]
+    pub fn bidirectional(mut self) -> Self {
+        self.bidirectional = true;
+        self
+    }
+
+    /// Coalesces the messages queued while writing to a connection into writes of up to `bytes` bytes,
+    /// instead of writing each message on its own.
+    /// See `ClientBuilder::max_write_batch` for details.
+    #[must_use]
+    pub fn max_write_batch(mut self, bytes: usize) -> Self {
+        self.max_write_batch = Some(bytes);
+        self
+    }
+
+    /// Accepts requests of up to `bytes` bytes, split across several frames when they don't fit in one.
+    /// See `ClientBuilder::max_message_size` for details.
+    ///
+    /// Responses are split across frames for clients asking for it, whether this is set or not.
+    #[must_use]
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Calls `hook` with every error accepting connections, in addition to logging them.
+    ///
+    /// Errors caused by exhausted resources (e.g., too many open files) make the server
+    /// back off from accepting connections, with an increasing delay of up to one second.
+    #[must_use]
+    pub fn on_accept_error(mut self, hook: impl Fn(&IoError) + Send + Sync + 'static) -> Self {
+        self.hooks.on_accept_error = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with every accepted connection, before any of its calls are handled.
+    ///
+    /// The hook can attach state to the connection, which handlers can then read
+    /// with `get_context().connection().extensions()`.
+    ///
+    /// ```no_run
+    /// # use trapeze::{get_context, Server};
+    /// struct Session {
+    ///     peer: String,
+    /// }
+    ///
+    /// let server = Server::new().on_connect(|conn| {
+    ///     let peer = conn.peer().unwrap_or_default().to_string();
+    ///     conn.extensions_mut().insert(Session { peer });
+    /// });
+    ///
+    /// // in a handler
+    /// let ctx = get_context();
+    /// let session = ctx.connection().extensions().get::<Session>();
+    /// ```
+    #[must_use]
+    pub fn on_connect(
+        mut self,
+        hook: impl Fn(&mut ConnectionInfo) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_connect = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a connection closes,
+    /// with the error that closed it, or `None` if it closed cleanly.
+    #[must_use]
+    pub fn on_disconnect(
+        mut self,
+        hook: impl Fn(&ConnectionInfo, Option<&IoError>) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_disconnect = Some(Arc::new(hook));
+        self
+    }
+
+    /// Adds an interceptor that runs for every call before its handler.
+    ///
+    /// Interceptors run in the order they are added, and can attach state to the call
+    /// with `ServerContext::extensions_mut`, or to its connection with `ServerContext::connection_mut`.
+    /// Returning an error rejects the call with that status, without running the handler.
+    ///
+    /// Interceptors run on the task that reads from the connection, so they should be quick.
+    ///
+    /// ```no_run
+    /// # use trapeze::{get_context, Server, Status};
+    /// struct Principal(String);
+    ///
+    /// let server = Server::new().interceptor(|ctx| {
+    ///     let Some(user) = ctx.metadata.get("user").and_then(|v| v.first()).cloned() else {
+    ///         return Err(Status::unauthenticated("Missing user"));
+    ///     };
+    ///     ctx.extensions_mut().insert(Principal(user));
+    ///     Ok(())
+    /// });
+    ///
+    /// // in a handler
+    /// let ctx = get_context();
+    /// let principal = ctx.extensions().get::<Principal>();
+    /// ```
+    #[must_use]
+    pub fn interceptor(
+        mut self,
+        interceptor: impl Fn(&mut ServerContext) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Calls `hook` when a call starts, before its handler runs.
+    #[must_use]
+    pub fn on_stream_open(mut self, hook: impl Fn(&ServerContext) + Send + Sync + 'static) -> Self {
+        self.hooks.on_stream_open = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a call finishes, with the error status the handler returned, if any.
+    #[must_use]
+    pub fn on_stream_close(
+        mut self,
+        hook: impl Fn(&ServerContext, Option<&Status>) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_stream_close = Some(Arc::new(hook));
+        self
+    }
+
     pub async fn bind(self, address: impl AsRef<str>) -> IoResult<ServerHandle> {
         let listener = bind(address).await?;
         Ok(self.start(listener))
     }
 
     pub fn start(mut self, mut listener: impl Listener) -> ServerHandle {
+        let hooks = Arc::new(std::mem::take(&mut self.hooks));
         ServerHandle::spawn(move |controller| async move {
             let shutdown = controller.shutdown.cancelled();
             pin_mut!(shutdown);
+
+            let mut backoff = AcceptBackoff::default();
+            let resume = sleep(Duration::ZERO);
+            pin_mut!(resume);
+
             loop {
+                let at_capacity = self
+                    .max_connections
+                    .is_some_and(|max| self.tasks.len() >= max);
+                let waiting = at_capacity && self.excess_connections == ExcessConnections::Wait;
+                let accepting = !backoff.is_paused() && !waiting;
+
                 tokio::select! {
-                    conn = listener.accept() => {
-                        let Ok(conn) = conn else {
-                            continue;
+                    conn = listener.accept_with_peer(), if accepting => {
+                        let (conn, peer) = match conn {
+                            Ok(conn) => conn,
+                            Err(err) => {
+                                log::warn!("Error accepting connection: {err}");
+                                hooks.accept_error(&err);
+                                if let Some(delay) = backoff.pause(&err) {
+                                    resume.as_mut().reset(Instant::now() + delay);
+                                }
+                                continue;
+                            }
                         };
+                        backoff.reset();
+                        if at_capacity {
+                            log::warn!("Closing new connection, reached the limit of open connections");
+                            continue;
+                        }
                         let methods = self.methods.clone();
                         let controller = controller.clone();
+                        let limits = self.limits;
+                        let hooks = hooks.clone();
+                        let bidirectional = self.bidirectional;
+                        let max_write_batch = self.max_write_batch;
+                        let max_message_size = self.max_message_size;
                         self.tasks.spawn(async move {
-                            ServerConnection::new_with_methods(conn, methods)
-                                .with_controller(controller)
-                                .start()
-                                .await
+                            let mut conn = if bidirectional {
+                                ServerConnection::new_bidirectional(conn, methods, 0, max_write_batch, max_message_size).0
+                            } else {
+                                ServerConnection::new_with_methods(conn, methods, max_write_batch, max_message_size)
+                            };
+                            conn.with_controller(controller)
+                                .with_peer(peer)
+                                .with_limits(limits)
+                                .with_hooks(hooks.clone());
+                            hooks.connect(conn.info_mut());
+                            let result = conn.start().await;
+                            hooks.disconnect(conn.info(), &result);
+                            result
                         });
                     },
+                    () = &mut resume, if backoff.is_paused() => backoff.resume(),
                     Some(res) = self.tasks.join_next() => {
                         handle_task_result(res?);
                     },
@@ -85,6 +359,54 @@ impl Server {
     }
 }
 
+// The delay before accepting connections again after an accept error
+#[derive(Default)]
+struct AcceptBackoff {
+    delay: Option<Duration>,
+    paused: bool,
+}
+
+impl AcceptBackoff {
+    const INITIAL_DELAY: Duration = Duration::from_millis(5);
+    const MAX_DELAY: Duration = Duration::from_secs(1);
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Returns the delay to wait before accepting again, if `err` calls for a backoff
+    fn pause(&mut self, err: &IoError) -> Option<Duration> {
+        // errors specific to a single connection don't affect accepting the next one
+        if matches!(
+            err.kind(),
+            ErrorKind::ConnectionAborted
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::TimedOut
+        ) {
+            return None;
+        }
+
+        let delay = match self.delay {
+            Some(delay) => (delay * 2).min(Self::MAX_DELAY),
+            None => Self::INITIAL_DELAY,
+        };
+        self.delay = Some(delay);
+        self.paused = true;
+        Some(delay)
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    fn reset(&mut self) {
+        self.delay = None;
+    }
+}
+
 fn handle_task_result(result: IoResult<()>) {
     match result {
         Err(err) if err.kind() == ErrorKind::UnexpectedEof => {}
@@ -95,10 +417,17 @@ fn handle_task_result(result: IoResult<()>) {
 
 pub struct ServerConnection {
     io: MessageIo,
-    methods: HashMap<&'static str, Arc<dyn MethodHandler + Send + Sync>>,
+    methods: Router,
     tasks: JoinSet<IoResult<()>>,
     io_tasks: JoinSet<IoResult<()>>,
     controller: ServerController,
+    info: Arc<ConnectionInfo>,
+    limits: ConnectionLimits,
+    hooks: Arc<Hooks>,
+    // The parity of the ids of the streams opened by the client
+    stream_id_parity: u32,
+    // Set once the connection reached its max age, to reject new calls
+    closing: bool,
 }
 
 impl ServerConnection {
@@ -110,12 +439,49 @@ impl ServerConnection {
         connection: C,
         services: impl IntoIterator<Item = &'a dyn Service>,
     ) -> ServerConnection {
-        let mut methods = HashMap::default();
+        let mut methods = Router::default();
         for service in services {
-            methods.extend(service.methods().into_iter());
+            methods.extend(service.methods());
         }
 
-        Self::new_with_methods(connection, methods)
+        Self::new_with_methods(connection, methods, None, None)
+    }
+
+    /// Uses `connection` both to handle calls from the client, and to make calls to it.
+    /// See `Server::bidirectional` for details.
+    ///
+    /// The returned client is also available to handlers with `get_context().connection().client()`.
+    pub fn bidirectional<C: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: C,
+    ) -> (ServerConnection, Client) {
+        Self::new_bidirectional(connection, Router::default(), 0, None, None)
+    }
+
+    // Creates a connection whose client opens streams with ids of parity `local_parity`,
+    // and whose server handles streams of the other parity
+    pub(crate) fn new_bidirectional<C: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: C,
+        methods: Router,
+        local_parity: u32,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
+    ) -> (ServerConnection, Client) {
+        let mut io_tasks = JoinSet::<IoResult<()>>::new();
+        let (local, remote) = MessageIo::bidirectional(
+            &mut io_tasks,
+            connection,
+            local_parity,
+            max_write_batch,
+            max_message_size,
+        );
+
+        let first_id = if local_parity == 1 { 1 } else { 2 };
+        let client = Client::with_io(local, first_id, max_message_size);
+
+        let mut conn = Self::with_io(remote, io_tasks, methods);
+        conn.stream_id_parity = 1 - local_parity;
+        conn.info_mut().set_client(client.clone());
+        (conn, client)
     }
 
     fn with_controller(&mut self, controller: ServerController) -> &mut Self {
@@ -123,13 +489,38 @@ impl ServerConnection {
         self
     }
 
+    fn with_peer(&mut self, peer: Option<String>) -> &mut Self {
+        self.info_mut().set_peer(peer.map(Into::into));
+        self
+    }
+
+    fn with_limits(&mut self, limits: ConnectionLimits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    fn with_hooks(&mut self, hooks: Arc<Hooks>) -> &mut Self {
+        self.hooks = hooks;
+        self
+    }
+
     fn new_with_methods<C: AsyncRead + AsyncWrite + Send + 'static>(
         connection: C,
-        methods: impl Into<HashMap<&'static str, Arc<dyn MethodHandler + Send + Sync>>>,
+        methods: Router,
+        max_write_batch: Option<usize>,
+        max_message_size: Option<usize>,
     ) -> ServerConnection {
         let mut io_tasks = JoinSet::<IoResult<()>>::new();
-        let io = MessageIo::new(&mut io_tasks, connection);
-        let methods = methods.into();
+        let io = MessageIo::new(&mut io_tasks, connection, max_write_batch, max_message_size);
+        Self::with_io(io, io_tasks, methods)
+    }
+
+    fn with_io(
+        mut io: MessageIo,
+        io_tasks: JoinSet<IoResult<()>>,
+        methods: Router,
+    ) -> ServerConnection {
+        io.rx.accept_streams();
         let controller = ServerController::default();
         let tasks = JoinSet::<IoResult<()>>::new();
 
@@ -139,6 +530,11 @@ impl ServerConnection {
             tasks,
             io_tasks,
             controller,
+            info: Arc::new(ConnectionInfo::new()),
+            limits: ConnectionLimits::default(),
+            hooks: Arc::default(),
+            stream_id_parity: 1,
+            closing: false,
         }
     }
 
@@ -148,10 +544,100 @@ impl ServerConnection {
         self
     }
 
+    /// Registers a raw method handler for `path`.
+    /// See `Server::register_method` for details.
+    pub fn register_method(&mut self, path: impl Into<String>, method: RawMethod) -> &mut Self {
+        self.methods.insert(path, method.0);
+        self
+    }
+
+    /// Registers the routes of `proxy`.
+    /// See `Server::register_proxy` for details.
+    pub fn register_proxy(&mut self, proxy: Proxy) -> &mut Self {
+        self.methods
+            .extend(proxy.into_methods().map(|(path, method)| (path, method.0)));
+        self
+    }
+
+    /// Registers a handler for calls to methods that are not otherwise registered.
+    /// See `raw::any` for details.
+    pub fn fallback<F, Fut>(&mut self, method: F) -> &mut Self
+    where
+        F: Fn(RawCall) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        self.register_method("*", raw::any(method))
+    }
+
+    /// Closes the connection once it has had no calls in progress for `max_idle_time`.
+    /// See `Server::max_idle_time` for details.
+    pub fn max_idle_time(&mut self, max_idle_time: Duration) -> &mut Self {
+        self.limits.max_idle_time = Some(max_idle_time);
+        self
+    }
+
+    /// Closes the connection once it's `max_connection_age` old.
+    /// See `Server::max_connection_age` for details.
+    pub fn max_connection_age(&mut self, max_connection_age: Duration) -> &mut Self {
+        self.limits.max_connection_age = Some(max_connection_age);
+        self
+    }
+
+    /// The connection that calls see through `get_context().connection()`.
+    #[must_use]
+    pub fn info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+
+    /// Allows attaching state to the connection before starting it.
+    pub fn info_mut(&mut self) -> &mut ConnectionInfo {
+        Arc::make_mut(&mut self.info)
+    }
+
+    /// Adds an interceptor that runs for every call before its handler.
+    /// See `Server::interceptor` for details.
+    pub fn interceptor(
+        &mut self,
+        interceptor: impl Fn(&mut ServerContext) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        Arc::make_mut(&mut self.hooks)
+            .interceptors
+            .push(Arc::new(interceptor));
+        self
+    }
+
+    /// Calls `hook` when a call starts. See `Server::on_stream_open` for details.
+    pub fn on_stream_open(
+        &mut self,
+        hook: impl Fn(&ServerContext) + Send + Sync + 'static,
+    ) -> &mut Self {
+        Arc::make_mut(&mut self.hooks).on_stream_open = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a call finishes. See `Server::on_stream_close` for details.
+    pub fn on_stream_close(
+        &mut self,
+        hook: impl Fn(&ServerContext, Option<&Status>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        Arc::make_mut(&mut self.hooks).on_stream_close = Some(Arc::new(hook));
+        self
+    }
+
     pub async fn start(&mut self) -> IoResult<()> {
         let shutdown = self.controller.shutdown.clone();
         let shutdown = shutdown.cancelled();
         pin_mut!(shutdown);
+
+        let ConnectionLimits {
+            max_idle_time,
+            max_connection_age,
+        } = self.limits;
+        let idle = sleep(max_idle_time.unwrap_or_default());
+        let age = sleep(max_connection_age.unwrap_or_default());
+        pin_mut!(idle);
+        pin_mut!(age);
+
         loop {
             tokio::select! {
                 Some(res) = self.io_tasks.join_next() => {
@@ -159,10 +645,22 @@ impl ServerConnection {
                 },
                 Some(res) = self.tasks.join_next() => {
                     res??;
+                    if let Some(max_idle_time) = max_idle_time {
+                        idle.as_mut().reset(Instant::now() + max_idle_time);
+                    }
                 },
-                Some((id, frame)) = self.io.rx.recv() => {
+                Some(frame) = self.io.rx.recv() => {
+                    let (id, frame) = frame?;
                     self.handle_message(id, &frame);
                 },
+                () = &mut idle, if max_idle_time.is_some() && self.tasks.is_empty() => {
+                    log::debug!("Closing idle connection");
+                    break;
+                },
+                () = &mut age, if max_connection_age.is_some() && !self.closing => {
+                    log::debug!("Closing connection after reaching its max age");
+                    self.closing = true;
+                },
                 () = &mut shutdown, if self.tasks.is_empty() => break,
                 else => {
                     // no more messages to read, and no more taks to process
@@ -170,6 +668,10 @@ impl ServerConnection {
                     break;
                 },
             }
+
+            if self.closing && self.tasks.is_empty() {
+                break;
+            }
         }
         Ok(())
     }
@@ -185,8 +687,10 @@ impl ServerConnection {
             return;
         };
 
-        if (id % 2) != 1 {
-            stream.tx.send(Status::invalid_stream_id(id));
+        if (id % 2) != self.stream_id_parity {
+            stream
+                .tx
+                .error(Status::invalid_stream_id(id, self.stream_id_parity));
             return;
         }
 
@@ -204,26 +708,87 @@ impl ServerConnection {
             metadata,
         } = req;
 
+        if self.closing {
+            // wait for the error to be sent before closing the connection
+            let status = Status::unavailable("The connection is closing");
+            self.tasks.spawn(async move {
+                let _ = stream.tx.error(status).await;
+                Ok(())
+            });
+            return;
+        }
+
+        if service == KEEPALIVE_SERVICE && method == KEEPALIVE_METHOD {
+            stream.tx.respond(());
+            return;
+        }
+
+        let path = format!("/{service}/{method}");
+
+        let Some(handler) = self.methods.get(path.as_str()).cloned() else {
+            stream.tx.error(Status::method_not_found(service, method));
+            return;
+        };
+
         let ctx = Context {
             metadata: metadata.as_slice().into(),
             timeout: Timeout::from_nanos(timeout_nano),
         };
+        let mut ctx = ServerContext::new(
+            ctx,
+            self.controller.clone(),
+            service,
+            method,
+            self.info.clone(),
+        );
+        let cancellation = ctx.cancellation_token();
 
-        let path = format!("/{service}/{method}");
+        if ctx.accepts_response_metadata {
+            stream
+                .tx
+                .set_response_metadata(ctx.response_metadata().clone());
+        }
+        stream.tx.set_compression(ctx.response_compression);
+        stream.tx.set_max_message_size(ctx.max_response_size);
 
-        let Some(method) = self.methods.get(path.as_str()).cloned() else {
-            stream.tx.error(Status::method_not_found(service, method));
-            return;
+        // handlers receive the payload decompressed, with the flags of an uncompressed request
+        let max_size = stream.rx.max_message_size();
+        let payload = match decompress(ctx.request_compression, flags, payload, max_size) {
+            Ok(payload) => payload,
+            Err(status) => {
+                stream.tx.error(status);
+                return;
+            }
         };
+        let flags = flags.difference(Flags::COMPRESSED);
+
+        let intercepted = self.hooks.intercept(&mut ctx);
+
+        // keep any state that the interceptors attached to the connection for later calls
+        if !Arc::ptr_eq(&self.info, &ctx.connection) {
+            self.info = ctx.connection.clone();
+        }
+
+        if let Err(status) = intercepted {
+            stream.tx.error(status);
+            return;
+        }
 
+        let hooks = self.hooks.clone();
+        let hook_ctx = ctx.clone();
         self.tasks.spawn(
             async move {
-                if let Err(status) = method.handle(flags, payload, &mut stream).await {
-                    stream.tx.error(status);
+                let _guard = cancellation.drop_guard();
+                let tx = stream.tx.clone();
+                hooks.stream_open(&hook_ctx);
+                let result = handler.handle(flags, payload, stream).await;
+                hooks.stream_close(&hook_ctx, result.as_ref().err());
+                if let Err(status) = result {
+                    tx.error(status);
                 }
                 Ok(())
             }
-            .with_context(ctx, self.controller.clone()),
+            .with_context(ctx),
         );
     }
 }
@@ -0,0 +1,236 @@
+//! Method handlers that work on undecoded payloads.
+//!
+//! Raw methods can be registered on a `Server` for an arbitrary path with `Server::register_method`.
+//! The called service and method are available through `get_context()`.
+//! Methods created with `any` handle calls of any kind, and can be used as a fallback with `Server::fallback`.
+//!
+//! ```no_run
+//! # use trapeze::{get_context, raw, Code, Server, Status};
+//! # async fn run() -> std::io::Result<()> {
+//! let server = Server::new()
+//!     .register_method("/ttrpc.test.streaming.Streaming/Echo", raw::unary(|payload| async move {
+//!         Ok(payload)
+//!     }))
+//!     .register_method("*", raw::unary(|_payload| async move {
+//!         let ctx = get_context();
+//!         let message = format!("/{}/{} is not supported", ctx.service(), ctx.method());
+//!         Err(Status::new(Code::NotFound, message))
+//!     }));
+//! server.bind("unix:///tmp/ttrpc-test").await?.await
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt as _};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::compression::decompress;
+use crate::context::metadata::Metadata;
+use crate::context::timeout::Timeout;
+use crate::context::{get_context, ServerContext};
+use crate::io::StreamIo;
+use crate::server::method_handlers::{handle_timeout, MethodHandler};
+use crate::server::router::Handler;
+use crate::service::{
+    ClientStreamingMethod, DuplexStreamingMethod, ServerStreamingMethod, UnaryMethod,
+};
+use crate::types::encoding::BufExt as _;
+pub use crate::types::flags::Flags;
+pub use crate::types::protos::raw_bytes::RawBytes;
+use crate::types::protos::Data;
+use crate::{Result, Status};
+
+/// A type-erased method handler, built with the functions in this module.
+#[derive(Clone)]
+pub struct RawMethod(pub(crate) Handler);
+
+pub fn unary<F, Fut>(method: F) -> RawMethod
+where
+    F: Fn(RawBytes) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<RawBytes>> + Send,
+{
+    RawMethod(Arc::new(UnaryMethod::<RawBytes, RawBytes, _>::new(method)))
+}
+
+pub fn server_streaming<F, Strm>(method: F) -> RawMethod
+where
+    F: Fn(RawBytes) -> Strm + Send + Sync + 'static,
+    Strm: Stream<Item = Result<RawBytes>> + Send,
+{
+    RawMethod(Arc::new(
+        ServerStreamingMethod::<RawBytes, RawBytes, _>::new(method),
+    ))
+}
+
+pub fn client_streaming<F, Fut>(method: F) -> RawMethod
+where
+    F: Fn(BoxStream<'static, RawBytes>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<RawBytes>> + Send,
+{
+    RawMethod(Arc::new(
+        ClientStreamingMethod::<RawBytes, RawBytes, _>::new(
+            move |input: UnboundedReceiverStream<_>| method(input.boxed()),
+        ),
+    ))
+}
+
+pub fn duplex_streaming<F, Strm>(method: F) -> RawMethod
+where
+    F: Fn(BoxStream<'static, RawBytes>) -> Strm + Send + Sync + 'static,
+    Strm: Stream<Item = Result<RawBytes>> + Send,
+{
+    RawMethod(Arc::new(
+        DuplexStreamingMethod::<RawBytes, RawBytes, _>::new(
+            move |input: UnboundedReceiverStream<_>| method(input.boxed()),
+        ),
+    ))
+}
+
+/// Returns a raw method that handles calls of any kind.
+///
+/// Unlike the other methods in this module, the flags of the request are not validated,
+/// and the method is responsible for finishing the call,
+/// either with `RawCall::respond`, with `RawCall::close`, or by returning an error.
+pub fn any<F, Fut>(method: F) -> RawMethod
+where
+    F: Fn(RawCall) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    RawMethod(Arc::new(AnyMethod { method }))
+}
+
+/// A call of any kind, as received by a method created with `any`.
+pub struct RawCall {
+    flags: Flags,
+    payload: RawBytes,
+    stream: StreamIo,
+    context: ServerContext,
+    input_closed: bool,
+}
+
+impl RawCall {
+    /// The fully qualified name of the called service, e.g., `ttrpc.test.streaming.Streaming`
+    pub fn service(&self) -> &str {
+        self.context.service()
+    }
+
+    /// The name of the called method, e.g., `EchoStream`
+    pub fn method(&self) -> &str {
+        self.context.method()
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.context.metadata
+    }
+
+    pub fn timeout(&self) -> Timeout {
+        self.context.timeout
+    }
+
+    /// The flags of the request frame
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Whether the client streams its input, to be read with `recv`
+    pub fn is_client_streaming(&self) -> bool {
+        self.flags.contains(Flags::REMOTE_OPEN)
+    }
+
+    /// The payload of the request.
+    /// This is empty for client streaming calls.
+    pub fn payload(&self) -> &RawBytes {
+        &self.payload
+    }
+
+    /// Receives the next message of the client stream.
+    /// Returns `None` once the client closes its stream, or if the client is not streaming.
+    pub async fn recv(&mut self) -> Result<Option<RawBytes>> {
+        while !self.input_closed {
+            let Some(frame) = self.stream.rx.recv().await else {
+                self.input_closed = true;
+                break;
+            };
+
+            let Data { payload } = frame
+                .message
+                .decode::<Data>()
+                .map_err(Status::failed_to_decode)?;
+
+            if frame.flags.contains(Flags::REMOTE_CLOSED) {
+                self.input_closed = true;
+            }
+
+            if frame.flags.contains(Flags::NO_DATA) {
+                payload.ensure_empty().map_err(Status::failed_to_decode)?;
+            } else {
+                let compression = self.context.request_compression;
+                let max_size = self.stream.rx.max_message_size();
+                return Ok(Some(decompress(
+                    compression,
+                    frame.flags,
+                    payload,
+                    max_size,
+                )?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finishes the call with a successful response.
+    pub async fn respond(&self, payload: RawBytes) -> Result<()> {
+        self.stream
+            .tx
+            .respond(payload)
+            .await
+            .map_err(Status::send_error)
+    }
+
+    /// Sends a message on the server stream.
+    pub async fn send(&self, payload: RawBytes) -> Result<()> {
+        self.stream
+            .tx
+            .data(payload)
+            .await
+            .map_err(Status::send_error)
+    }
+
+    /// Finishes the call by closing the server stream.
+    pub async fn close(&self) -> Result<()> {
+        self.stream
+            .tx
+            .close_data()
+            .await
+            .map_err(Status::send_error)
+    }
+}
+
+struct AnyMethod<F> {
+    method: F,
+}
+
+#[async_trait]
+impl<F, Fut> MethodHandler for AnyMethod<F>
+where
+    F: Fn(RawCall) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    async fn handle(&self, flags: Flags, payload: RawBytes, stream: StreamIo) -> Result<()> {
+        let call = RawCall {
+            flags,
+            payload,
+            stream,
+            context: get_context(),
+            input_closed: !flags.contains(Flags::REMOTE_OPEN),
+        };
+
+        tokio::select! {
+            res = (self.method)(call) => res,
+            res = handle_timeout() => res,
+        }
+    }
+}
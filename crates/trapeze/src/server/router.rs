@@ -0,0 +1,54 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::server::method_handlers::MethodHandler;
+
+pub type Handler = Arc<dyn MethodHandler + Send + Sync>;
+
+// Maps request paths (e.g., `/ttrpc.test.streaming.Streaming/Echo`) to method handlers.
+// Paths ending in `*` match any request path starting with the preceding prefix,
+// e.g., `/ttrpc.test.streaming.Streaming/*` or `*`.
+// Exact matches take precedence over prefix matches, and longer prefixes over shorter ones.
+#[derive(Default, Clone)]
+pub struct Router {
+    exact: HashMap<String, Handler>,
+    // sorted by descending prefix length
+    prefixes: Vec<(String, Handler)>,
+}
+
+impl Router {
+    pub fn insert(&mut self, path: impl Into<String>, handler: Handler) {
+        let path = path.into();
+        let Some(prefix) = path.strip_suffix('*') else {
+            self.exact.insert(path, handler);
+            return;
+        };
+
+        let prefix = prefix.to_string();
+        match self.prefixes.iter_mut().find(|(p, _)| *p == prefix) {
+            Some((_, h)) => *h = handler,
+            None => {
+                self.prefixes.push((prefix, handler));
+                self.prefixes.sort_by_key(|(p, _)| Reverse(p.len()));
+            }
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Handler> {
+        self.exact.get(path).or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+                .map(|(_, handler)| handler)
+        })
+    }
+}
+
+impl<P: Into<String>> Extend<(P, Handler)> for Router {
+    fn extend<T: IntoIterator<Item = (P, Handler)>>(&mut self, iter: T) {
+        for (path, handler) in iter {
+            self.insert(path, handler);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::future::{ready, Future};
+
+use async_stream::try_stream;
+use futures::future::FusedFuture as _;
+use futures::{FutureExt as _, Stream, StreamExt as _};
+use tokio::pin;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{Code, Result, Status};
+
+// The number of responses buffered before `ResponseSink::send` waits for them to be sent
+const BUFFER_SIZE: usize = 16;
+
+/// The sending half of the responses of a server streaming call,
+/// used by services generated with `Config::response_sinks`.
+///
+/// Sending waits while the responses sent earlier are still being written,
+/// and fails once the call is no longer receiving responses, e.g., because the client went away.
+/// The sink can be cloned to send responses from several producers.
+/// The call finishes once the handler returned and all the clones of the sink are dropped.
+pub struct ResponseSink<Output> {
+    tx: Sender<Result<Output>>,
+}
+
+impl<Output> Clone for ResponseSink<Output> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<Output> ResponseSink<Output> {
+    fn new() -> (Self, Receiver<Result<Output>>) {
+        let (tx, rx) = channel(BUFFER_SIZE);
+        (Self { tx }, rx)
+    }
+
+    /// Creates a sink, and the stream of the responses sent to it.
+    ///
+    /// Clients use it to call server streaming methods of services generated with `Config::response_sinks`.
+    ///
+    /// ```no_run
+    /// # use trapeze::{ResponseSink, Result};
+    /// # use futures::StreamExt as _;
+    /// # async fn divide_stream(sum: u32, sink: ResponseSink<u32>) -> Result<()> { Ok(()) }
+    /// # async fn run() -> Result<()> {
+    /// let (sink, mut parts) = ResponseSink::channel();
+    /// tokio::spawn(divide_stream(10, sink));
+    /// while let Some(part) = parts.next().await {
+    ///     let part = part?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn channel() -> (Self, impl Stream<Item = Result<Output>>) {
+        let (sink, rx) = Self::new();
+        let responses = ReceiverStream::new(rx).take_while(|response| ready(!is_end(response)));
+        (sink, responses)
+    }
+
+    /// Sends a response, waiting while the buffer of responses is full.
+    pub async fn send(&self, message: Output) -> Result<()> {
+        self.tx
+            .send(Ok(message))
+            .await
+            .map_err(|_| Status::cancelled("The call is no longer receiving responses"))
+    }
+
+    /// Finishes the call with `status`.
+    /// Closing with `Code::Ok` ends the responses successfully, even if clones of the sink are left.
+    pub async fn close(self, status: Status) {
+        let _ = self.tx.send(Err(status)).await;
+    }
+
+    /// Whether the call is no longer receiving responses.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Waits until the call is no longer receiving responses, e.g., because the client went away.
+    pub async fn closed(&self) {
+        self.tx.closed().await;
+    }
+}
+
+// Whether `response` ends the responses successfully, sent by closing the sink with `Code::Ok`
+fn is_end<Output>(response: &Result<Output>) -> bool {
+    matches!(response, Err(status) if status.code() == Code::Ok)
+}
+
+// Turns a handler writing to a sink into the stream of its responses
+pub fn sink_stream<Output, Fut: Future<Output = Result<()>>>(
+    handler: impl FnOnce(ResponseSink<Output>) -> Fut,
+) -> impl Stream<Item = Result<Output>> {
+    let (sink, mut rx) = ResponseSink::new();
+    let fut = handler(sink);
+
+    enum Next<Output> {
+        Response(Result<Output>),
+        Finished(Result<()>),
+        Done,
+    }
+
+    try_stream! {
+        let fut = fut.fuse();
+        pin!(fut);
+        loop {
+            let next = tokio::select! {
+                biased;
+                Some(response) = rx.recv() => Next::Response(response),
+                res = &mut fut, if !fut.is_terminated() => Next::Finished(res),
+                else => Next::Done,
+            };
+            match next {
+                Next::Response(response) if is_end(&response) => break,
+                Next::Response(response) => yield response?,
+                Next::Finished(Ok(())) => continue,
+                Next::Finished(Err(status)) => {
+                    // send the responses sent before the error
+                    while let Ok(response) = rx.try_recv() {
+                        if is_end(&response) {
+                            break;
+                        }
+                        yield response?;
+                    }
+                    Err(status)?;
+                }
+                Next::Done => break,
+            }
+        }
+    }
+}
+
+// Forwards the responses of a call to a sink, for clients of services using sinks
+pub async fn forward_to_sink<Output>(
+    responses: impl Stream<Item = Result<Output>>,
+    sink: ResponseSink<Output>,
+) -> Result<()> {
+    pin!(responses);
+    while let Some(response) = responses.next().await {
+        match response {
+            Ok(message) => sink.send(message).await?,
+            Err(status) => {
+                sink.close(status.clone()).await;
+                return Err(status);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::stream;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    async fn collect<Output>(responses: impl Stream<Item = Result<Output>>) -> Vec<Result<Output>> {
+        responses.collect().await
+    }
+
+    #[tokio::test]
+    async fn streams_the_sent_responses() {
+        let responses = sink_stream(|sink| async move {
+            sink.send(1).await?;
+            // clones keep the call going after the handler returned
+            let sink = sink.clone();
+            tokio::spawn(async move { sink.send(2).await });
+            Ok(())
+        });
+
+        let responses = collect(responses).await;
+        assert_eq!(responses, [Ok(1), Ok(2)]);
+    }
+
+    #[tokio::test]
+    async fn sends_responses_before_the_error() {
+        let responses = sink_stream(|sink| async move {
+            sink.send(1).await?;
+            Err(Status::not_found("Missing"))
+        });
+
+        let responses = collect(responses).await;
+        assert_eq!(responses, [Ok(1), Err(Status::not_found("Missing"))]);
+    }
+
+    #[tokio::test]
+    async fn finishes_with_the_closing_status() {
+        let responses = sink_stream(|sink| async move {
+            sink.send(1).await?;
+            sink.close(Status::aborted("Stop")).await;
+            Ok(())
+        });
+
+        let responses = collect(responses).await;
+        assert_eq!(responses, [Ok(1), Err(Status::aborted("Stop"))]);
+    }
+
+    #[tokio::test]
+    async fn finishes_successfully_when_closed_with_ok() {
+        let responses = sink_stream(|sink| async move {
+            sink.send(1).await?;
+            // the clone doesn't keep the call going
+            let _clone = sink.clone();
+            sink.close(Status::new(Code::Ok, "")).await;
+            std::future::pending().await
+        });
+
+        let responses = timeout(Duration::from_secs(5), collect(responses)).await;
+        assert_eq!(responses.unwrap(), [Ok(1)]);
+
+        let (sink, responses) = ResponseSink::channel();
+        sink.send(1).await.unwrap();
+        sink.clone().close(Status::new(Code::Ok, "")).await;
+
+        let responses = timeout(Duration::from_secs(5), collect(responses)).await;
+        assert_eq!(responses.unwrap(), [Ok(1)]);
+    }
+
+    #[tokio::test]
+    async fn fails_once_the_call_went_away() {
+        let (sink, responses) = ResponseSink::channel();
+        assert!(!sink.is_closed());
+        drop(responses);
+
+        timeout(Duration::from_secs(5), sink.closed())
+            .await
+            .unwrap();
+        assert!(sink.is_closed());
+        let status = sink.send(1).await.unwrap_err();
+        assert_eq!(status.code(), Code::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn waits_while_the_buffer_is_full() {
+        let (sink, responses) = ResponseSink::channel();
+        for i in 0..BUFFER_SIZE {
+            sink.send(i).await.unwrap();
+        }
+
+        let pending = timeout(Duration::from_millis(50), sink.send(BUFFER_SIZE)).await;
+        assert!(pending.is_err());
+
+        pin!(responses);
+        assert_eq!(responses.next().await, Some(Ok(0)));
+        timeout(Duration::from_secs(5), sink.send(BUFFER_SIZE))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn forwards_responses_to_sinks() {
+        let (sink, responses) = ResponseSink::channel();
+        let forwarded = stream::iter([Ok(1), Err(Status::internal("Broken")), Ok(2)]);
+
+        let res = forward_to_sink(forwarded, sink).await;
+        assert_eq!(res, Err(Status::internal("Broken")));
+        let responses = collect(responses).await;
+        assert_eq!(responses, [Ok(1), Err(Status::internal("Broken"))]);
+    }
+}
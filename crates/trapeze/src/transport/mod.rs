@@ -22,6 +22,12 @@ impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> Connection for T {}
 #[async_trait]
 pub trait Listener: Send + 'static {
     async fn accept(&mut self) -> IoResult<Box<dyn Connection>>;
+
+    /// Accepts a new connection, along with the address of the peer if known,
+    /// e.g., `tcp://127.0.0.1:50000` or `vsock://3:1024`.
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        Ok((self.accept().await?, None))
+    }
 }
 
 #[async_trait]
@@ -29,6 +35,10 @@ impl Listener for Box<dyn Listener> {
     async fn accept(&mut self) -> IoResult<Box<dyn Connection>> {
         self.deref_mut().accept().await
     }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        self.deref_mut().accept_with_peer().await
+    }
 }
 
 pub async fn bind(addr: impl AsRef<str>) -> IoResult<impl Listener> {
@@ -1,6 +1,9 @@
 use std::io::Result as IoResult;
+use std::net::SocketAddr;
 
 use async_trait::async_trait;
+use socket2::SockRef;
+pub use socket2::TcpKeepalive;
 use tokio::net::{TcpListener, TcpStream};
 
 use super::{Connection, Listener};
@@ -11,6 +14,11 @@ impl Listener for TcpListener {
         let (conn, _) = TcpListener::accept(self).await?;
         Ok(Box::new(conn))
     }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        let (conn, addr) = TcpListener::accept(self).await?;
+        Ok((Box::new(conn), Some(format!("tcp://{addr}"))))
+    }
 }
 
 pub async fn bind(addr: impl AsRef<str>) -> IoResult<impl Listener> {
@@ -20,3 +28,101 @@ pub async fn bind(addr: impl AsRef<str>) -> IoResult<impl Listener> {
 pub async fn connect(addr: impl AsRef<str>) -> IoResult<impl Connection> {
     TcpStream::connect(addr.as_ref()).await
 }
+
+/// A listener that enables TCP keepalive on the connections it accepts.
+pub struct KeepaliveListener {
+    listener: TcpListener,
+    keepalive: TcpKeepalive,
+}
+
+#[async_trait]
+impl Listener for KeepaliveListener {
+    async fn accept(&mut self) -> IoResult<Box<dyn Connection>> {
+        Ok(self.accept_with_peer().await?.0)
+    }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        let (conn, addr) = self.accept_tcp().await?;
+        Ok((Box::new(conn), Some(format!("tcp://{addr}"))))
+    }
+}
+
+impl KeepaliveListener {
+    async fn accept_tcp(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
+        let (conn, addr) = self.listener.accept().await?;
+        SockRef::from(&conn).set_tcp_keepalive(&self.keepalive)?;
+        Ok((conn, addr))
+    }
+}
+
+/// Like `bind`, but enables TCP keepalive on accepted connections,
+/// so that the OS notices peers that went away without closing the connection.
+pub async fn bind_with_keepalive(
+    addr: impl AsRef<str>,
+    keepalive: TcpKeepalive,
+) -> IoResult<KeepaliveListener> {
+    let listener = TcpListener::bind(addr.as_ref()).await?;
+    Ok(KeepaliveListener {
+        listener,
+        keepalive,
+    })
+}
+
+/// Like `connect`, but enables TCP keepalive on the connection.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use trapeze::transport::tcp::{connect_with_keepalive, TcpKeepalive};
+/// # use trapeze::Client;
+/// # async fn run() -> std::io::Result<()> {
+/// let keepalive = TcpKeepalive::new()
+///     .with_time(Duration::from_secs(30))
+///     .with_interval(Duration::from_secs(5));
+/// let conn = connect_with_keepalive("127.0.0.1:1234", &keepalive).await?;
+/// let client = Client::new(conn);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn connect_with_keepalive(
+    addr: impl AsRef<str>,
+    keepalive: &TcpKeepalive,
+) -> IoResult<TcpStream> {
+    let conn = TcpStream::connect(addr.as_ref()).await?;
+    SockRef::from(&conn).set_tcp_keepalive(keepalive)?;
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn keepalive() -> TcpKeepalive {
+        TcpKeepalive::new().with_time(Duration::from_secs(30))
+    }
+
+    #[tokio::test]
+    async fn enables_keepalive_on_accepted_connections() {
+        let mut listener = bind_with_keepalive("127.0.0.1:0", keepalive())
+            .await
+            .unwrap();
+        let addr = listener.listener.local_addr().unwrap();
+
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let (conn, _) = listener.accept_tcp().await.unwrap();
+        assert!(SockRef::from(&conn).keepalive().unwrap());
+    }
+
+    #[tokio::test]
+    async fn enables_keepalive_on_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let conn = TcpStream::connect(&addr).await.unwrap();
+        assert!(!SockRef::from(&conn).keepalive().unwrap());
+
+        let conn = connect_with_keepalive(&addr, &keepalive()).await.unwrap();
+        assert!(SockRef::from(&conn).keepalive().unwrap());
+    }
+}
@@ -15,6 +15,11 @@ impl Listener for UnixListener {
         let (conn, _) = UnixListener::accept(self).await?;
         Ok(Box::new(conn))
     }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        let (conn, addr) = UnixListener::accept(self).await?;
+        Ok((Box::new(conn), peer_addr(&addr)))
+    }
 }
 
 pub fn bind(addr: impl AsRef<str>) -> IoResult<impl Listener> {
@@ -50,6 +55,10 @@ impl Listener for RaiiListener {
         let (conn, _) = UnixListener::accept(&self.inner).await?;
         Ok(Box::new(conn))
     }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn Connection>, Option<String>)> {
+        self.inner.accept_with_peer().await
+    }
 }
 
 impl Drop for RaiiListener {
@@ -69,6 +78,12 @@ fn make_socket_addr(addr: &str) -> IoResult<SocketAddr> {
     SocketAddr::from_pathname(addr)
 }
 
+// Client sockets are usually unnamed, in which case there's no address to report
+fn peer_addr(addr: &tokio::net::unix::SocketAddr) -> Option<String> {
+    let path = addr.as_pathname()?;
+    Some(format!("unix://{}", path.display()))
+}
+
 fn cleanup_socket(addr: &str) {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     if addr.strip_prefix('@').is_some() {
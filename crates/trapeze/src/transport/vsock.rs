@@ -9,6 +9,12 @@ impl super::Listener for VsockListener {
         let (conn, _) = VsockListener::accept(self).await?;
         Ok(Box::new(conn))
     }
+
+    async fn accept_with_peer(&mut self) -> IoResult<(Box<dyn super::Connection>, Option<String>)> {
+        let (conn, addr) = VsockListener::accept(self).await?;
+        let peer = format!("vsock://{}:{}", addr.cid(), addr.port());
+        Ok((Box::new(conn), Some(peer)))
+    }
 }
 
 pub fn bind(addr: impl AsRef<str>) -> IoResult<impl super::Listener> {
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use std::io::IoSlice;
+
+use prost::bytes::buf::UninitSlice;
+use prost::bytes::{Buf, BufMut, Bytes, BytesMut};
+
+// Buffers of at least this size are kept as separate chunks instead of being copied
+const MIN_CHUNK_SIZE: usize = 4 << 10;
+
+// The maximum capacity allocated upfront, as large payloads don't need to be copied
+const MAX_INITIAL_CAPACITY: usize = 8 << 10;
+
+/// A buffer that keeps large `Bytes` written to it as separate chunks, instead of copying them,
+/// so that they can be written with vectored writes.
+#[derive(Debug, Default)]
+pub struct ChunkedBytesMut {
+    chunks: VecDeque<Bytes>,
+    current: BytesMut,
+}
+
+/// The frozen chunks of a `ChunkedBytesMut`.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkedBytes {
+    chunks: VecDeque<Bytes>,
+}
+
+impl ChunkedBytesMut {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::default(),
+            current: BytesMut::with_capacity(capacity.min(MAX_INITIAL_CAPACITY)),
+        }
+    }
+
+    fn split_current(&mut self) {
+        if !self.current.is_empty() {
+            self.chunks.push_back(self.current.split().freeze());
+        }
+    }
+
+    /// The number of bytes written to the buffer.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(Bytes::len).sum::<usize>() + self.current.len()
+    }
+
+    /// Appends `bytes`, copying its small chunks, and keeping its large chunks as they are.
+    pub fn append(&mut self, bytes: ChunkedBytes) {
+        for chunk in bytes.chunks {
+            self.put(chunk);
+        }
+    }
+
+    /// Takes the bytes written so far, keeping the spare capacity of the buffer for further writes.
+    pub fn split(&mut self) -> ChunkedBytes {
+        self.split_current();
+        ChunkedBytes {
+            chunks: std::mem::take(&mut self.chunks),
+        }
+    }
+
+    pub fn freeze(mut self) -> ChunkedBytes {
+        self.split()
+    }
+}
+
+unsafe impl BufMut for ChunkedBytesMut {
+    fn remaining_mut(&self) -> usize {
+        self.current.remaining_mut()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.current.advance_mut(cnt);
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        self.current.chunk_mut()
+    }
+
+    fn put<T: Buf>(&mut self, mut src: T) {
+        if src.remaining() < MIN_CHUNK_SIZE {
+            while src.has_remaining() {
+                let chunk = src.chunk();
+                let len = chunk.len();
+                self.current.put_slice(chunk);
+                src.advance(len);
+            }
+            return;
+        }
+
+        // this doesn't copy if `src` is `Bytes`
+        self.split_current();
+        self.chunks.push_back(src.copy_to_bytes(src.remaining()));
+    }
+
+    fn put_slice(&mut self, src: &[u8]) {
+        self.current.put_slice(src);
+    }
+}
+
+impl ChunkedBytes {
+    /// Splits off the first `at` bytes, without copying them.
+    pub fn split_to(&mut self, mut at: usize) -> ChunkedBytes {
+        let mut chunks = VecDeque::new();
+        while at > 0 {
+            let Some(chunk) = self.chunks.front_mut() else {
+                panic!("cannot split past the end of the buffer");
+            };
+            if at < chunk.len() {
+                chunks.push_back(chunk.split_to(at));
+                break;
+            }
+            at -= chunk.len();
+            chunks.extend(self.chunks.pop_front());
+        }
+        ChunkedBytes { chunks }
+    }
+}
+
+impl Buf for ChunkedBytes {
+    fn remaining(&self) -> usize {
+        self.chunks.iter().map(Bytes::len).sum()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.chunks.front().map_or(&[], |chunk| chunk.as_ref())
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while cnt > 0 {
+            let Some(chunk) = self.chunks.front_mut() else {
+                panic!("cannot advance past the end of the buffer");
+            };
+            if cnt < chunk.len() {
+                chunk.advance(cnt);
+                return;
+            }
+            cnt -= chunk.len();
+            self.chunks.pop_front();
+        }
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let mut n = 0;
+        for (dst, chunk) in dst.iter_mut().zip(&self.chunks) {
+            *dst = IoSlice::new(chunk);
+            n += 1;
+        }
+        n
+    }
+}
@@ -1,6 +1,6 @@
 use prost::bytes::{BufMut, Bytes, BytesMut};
 
-use super::{BufMutExt, EncodeError, InvalidInput};
+use super::{BufMutExt, ChunkedBytes, ChunkedBytesMut, EncodeError, InvalidInput};
 
 pub trait Encodeable: std::fmt::Debug {
     fn encode_raw(&self, buf: &mut impl BufMut) -> Result<(), InvalidInput>;
@@ -18,6 +18,13 @@ pub trait Encodeable: std::fmt::Debug {
         self.encode_raw(&mut buf)?;
         Ok(buf.into())
     }
+
+    // Like `encode_to_bytes`, but without copying large `Bytes` fields
+    fn encode_to_chunks(&self) -> Result<ChunkedBytes, InvalidInput> {
+        let mut buf = ChunkedBytesMut::with_capacity(self.encoded_len());
+        self.encode_raw(&mut buf)?;
+        Ok(buf.freeze())
+    }
 }
 
 impl<T: prost::Message> Encodeable for T {
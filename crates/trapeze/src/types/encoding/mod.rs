@@ -1,10 +1,12 @@
 pub mod buf;
+pub mod chunked;
 pub mod decodeable;
 pub mod encodeable;
 pub mod error;
 pub mod fallible_buf;
 
 pub use buf::{BufExt, BufMutExt};
+pub use chunked::{ChunkedBytes, ChunkedBytesMut};
 pub use decodeable::Decodeable;
 pub use encodeable::Encodeable;
 pub use error::{DecodeError, EncodeError, InvalidInput};
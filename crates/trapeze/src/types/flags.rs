@@ -6,5 +6,9 @@ bitflags! {
         const REMOTE_CLOSED = 0x01;
         const REMOTE_OPEN = 0x02;
         const NO_DATA = 0x04;
+        // The payload of the frame is compressed, see `compression`
+        const COMPRESSED = 0x08;
+        // More fragments of the message follow in the next frames of the stream, see `io::fragments`
+        const FRAGMENT = 0x10;
     }
 }
@@ -5,13 +5,15 @@ use std::io::Result as IoResult;
 use prost::bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt as _};
 
-use crate::types::encoding::{BufExt as _, DecodeError, Decodeable, Encodeable, InvalidInput};
+use crate::types::encoding::{
+    BufExt as _, ChunkedBytes, ChunkedBytesMut, DecodeError, Decodeable, Encodeable, InvalidInput,
+};
 use crate::types::flags::Flags;
 use crate::types::message::{FallibleBytesMessage, Message};
 use crate::types::protos::raw_bytes::ProstField;
 use crate::types::protos::{Response, Status};
 
-const MAX_DATA_LENGTH: usize = 4 << 20;
+pub const MAX_DATA_LENGTH: usize = 4 << 20;
 const HEADER_LENGTH: usize = 10;
 const DISCARD_PAGE_SIZE: usize = 4 << 10;
 
@@ -60,19 +62,16 @@ impl From<Status> for StreamFrame<Response<()>> {
 }
 
 impl<Msg: Message + Encodeable> Encodeable for Frame<Msg> {
-    fn encode_raw(&self, mut buf: &mut impl BufMut) -> Result<(), InvalidInput> {
+    fn encode_raw(&self, buf: &mut impl BufMut) -> Result<(), InvalidInput> {
         let length = self.message.encoded_len();
         if length > MAX_DATA_LENGTH {
             let msg = format!("Oversized payload: {length} bytes > {MAX_DATA_LENGTH} bytes");
             return Err(msg.into());
         }
 
-        #[allow(clippy::cast_possible_truncation)]
-        buf.put_u32(length as u32);
-        buf.put_u32(self.id);
-        buf.put_u8(u8::from(Msg::TYPE_ID));
-        buf.put_u8(self.flags.bits());
-        self.message.encode_raw(&mut buf)?;
+        put_header::<Msg>(buf, length, self.id, self.flags);
+        // pass `buf` on as is, so that buffers can handle large `Bytes` fields without copying them
+        self.message.encode_raw(buf)?;
 
         Ok(())
     }
@@ -82,6 +81,43 @@ impl<Msg: Message + Encodeable> Encodeable for Frame<Msg> {
     }
 }
 
+impl<Msg: Message + Encodeable> Frame<Msg> {
+    /// Encodes the frame, splitting messages that don't fit in a frame across several frames,
+    /// all but the last one with the `FRAGMENT` flag, for messages of up to `max_message_size` bytes.
+    pub fn encode_fragments(&self, max_message_size: usize) -> Result<ChunkedBytes, InvalidInput> {
+        let length = self.message.encoded_len();
+        if length <= MAX_DATA_LENGTH {
+            return self.encode_to_chunks();
+        }
+        if length > max_message_size {
+            let msg = format!("Oversized message: {length} bytes > {max_message_size} bytes");
+            return Err(msg.into());
+        }
+
+        let mut message = self.message.encode_to_chunks()?;
+        let mut buf = ChunkedBytesMut::default();
+        while message.has_remaining() {
+            let fragment = message.split_to(message.remaining().min(MAX_DATA_LENGTH));
+            let flags = if message.has_remaining() {
+                self.flags | Flags::FRAGMENT
+            } else {
+                self.flags
+            };
+            put_header::<Msg>(&mut buf, fragment.remaining(), self.id, flags);
+            buf.append(fragment);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+fn put_header<Msg: Message>(buf: &mut impl BufMut, length: usize, id: u32, flags: Flags) {
+    #[allow(clippy::cast_possible_truncation)]
+    buf.put_u32(length as u32);
+    buf.put_u32(id);
+    buf.put_u8(u8::from(Msg::TYPE_ID));
+    buf.put_u8(flags.bits());
+}
+
 impl Decodeable for Frame<FallibleBytesMessage> {
     fn decode_raw(mut buf: impl Buf) -> Result<Self, DecodeError> {
         buf.ensure_remaining(HEADER_LENGTH)?;
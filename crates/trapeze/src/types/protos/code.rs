@@ -216,3 +216,30 @@ impl Code {
         }
     }
 }
+
+impl From<std::io::ErrorKind> for Code {
+    fn from(kind: std::io::ErrorKind) -> Self {
+        use std::io::ErrorKind;
+        match kind {
+            ErrorKind::NotFound => Code::NotFound,
+            ErrorKind::PermissionDenied => Code::PermissionDenied,
+            ErrorKind::AlreadyExists => Code::AlreadyExists,
+            ErrorKind::TimedOut => Code::DeadlineExceeded,
+            ErrorKind::Interrupted => Code::Cancelled,
+            ErrorKind::InvalidInput => Code::InvalidArgument,
+            ErrorKind::InvalidData => Code::DataLoss,
+            ErrorKind::UnexpectedEof => Code::OutOfRange,
+            ErrorKind::Unsupported => Code::Unimplemented,
+            ErrorKind::OutOfMemory => Code::ResourceExhausted,
+            ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::AddrInUse
+            | ErrorKind::AddrNotAvailable
+            | ErrorKind::BrokenPipe => Code::Unavailable,
+            _ => Code::Internal,
+        }
+    }
+}
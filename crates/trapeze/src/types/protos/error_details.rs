@@ -0,0 +1,391 @@
+//! Standard error details, as defined in [`google/rpc/error_details.proto`][1].
+//!
+//! Error details are attached to a `Status` with `Status::with_detail`,
+//! and extracted with `Status::get_detail`.
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use trapeze::error_details::{ErrorInfo, RetryInfo};
+//! # use trapeze::Status;
+//! let status = Status::unavailable("The agent is starting")
+//!     .with_detail(ErrorInfo::new("AGENT_STARTING", "agent.example.com").with_metadata("pid", "42"))
+//!     .with_detail(RetryInfo::new(Duration::from_millis(500)));
+//!
+//! let info: ErrorInfo = status.get_detail().unwrap();
+//! assert_eq!(info.reason, "AGENT_STARTING");
+//! ```
+//!
+//! [1]: https://github.com/googleapis/googleapis/blob/master/google/rpc/error_details.proto
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use prost::Name;
+
+macro_rules! impl_name {
+    ($ty:ty, $name:literal) => {
+        impl Name for $ty {
+            const NAME: &'static str = $name;
+            const PACKAGE: &'static str = "google.rpc";
+
+            fn type_url() -> String {
+                format!("type.googleapis.com/{}", Self::full_name())
+            }
+        }
+    };
+}
+
+/// Describes the cause of the error with structured details.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ErrorInfo {
+    /// The reason of the error, e.g., `API_DISABLED`.
+    #[prost(string, tag = "1")]
+    pub reason: String,
+
+    /// The logical grouping to which the `reason` belongs, e.g., `agent.example.com`.
+    #[prost(string, tag = "2")]
+    pub domain: String,
+
+    /// Additional structured details about this error.
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl_name!(ErrorInfo, "ErrorInfo");
+
+impl ErrorInfo {
+    /// Creates an error info with the `reason` of the error, within `domain`.
+    #[must_use]
+    pub fn new(reason: impl Into<String>, domain: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            domain: domain.into(),
+            metadata: HashMap::default(),
+        }
+    }
+
+    /// Adds a structured detail about the error.
+    #[must_use]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Describes when the clients can retry a failed request.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetryInfo {
+    /// Clients should wait at least this long between retrying the same request.
+    #[prost(message, optional, tag = "1")]
+    pub retry_delay: Option<prost_types::Duration>,
+}
+
+impl_name!(RetryInfo, "RetryInfo");
+
+impl RetryInfo {
+    /// Creates a retry info asking clients to wait `retry_delay` before retrying.
+    #[must_use]
+    pub fn new(retry_delay: Duration) -> Self {
+        let retry_delay = prost_types::Duration::try_from(retry_delay).ok();
+        Self { retry_delay }
+    }
+
+    /// The retry delay, if it's set and not negative.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        self.retry_delay.and_then(|d| Duration::try_from(d).ok())
+    }
+}
+
+/// Describes additional debugging info.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DebugInfo {
+    /// The stack trace entries indicating where the error occurred.
+    #[prost(string, repeated, tag = "1")]
+    pub stack_entries: Vec<String>,
+
+    /// Additional debugging information provided by the server.
+    #[prost(string, tag = "2")]
+    pub detail: String,
+}
+
+impl_name!(DebugInfo, "DebugInfo");
+
+impl DebugInfo {
+    /// Creates a debug info with a `detail` message and no stack entries.
+    #[must_use]
+    pub fn new(detail: impl Into<String>) -> Self {
+        Self {
+            stack_entries: vec![],
+            detail: detail.into(),
+        }
+    }
+
+    /// Appends entries to the stack trace.
+    #[must_use]
+    pub fn with_stack_entries(
+        mut self,
+        stack_entries: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.stack_entries
+            .extend(stack_entries.into_iter().map(Into::into));
+        self
+    }
+}
+
+/// Describes how a quota check failed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QuotaFailure {
+    /// Describes all quota violations.
+    #[prost(message, repeated, tag = "1")]
+    pub violations: Vec<quota_failure::Violation>,
+}
+
+impl_name!(QuotaFailure, "QuotaFailure");
+
+pub mod quota_failure {
+    /// A single quota violation.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Violation {
+        /// The subject on which the quota check failed, e.g., `container:abc123`.
+        #[prost(string, tag = "1")]
+        pub subject: String,
+
+        /// A description of how the quota check failed.
+        #[prost(string, tag = "2")]
+        pub description: String,
+    }
+}
+
+impl QuotaFailure {
+    /// Appends a quota violation on `subject`.
+    #[must_use]
+    pub fn with_violation(
+        mut self,
+        subject: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.violations.push(quota_failure::Violation {
+            subject: subject.into(),
+            description: description.into(),
+        });
+        self
+    }
+}
+
+/// Describes what preconditions have failed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PreconditionFailure {
+    /// Describes all precondition violations.
+    #[prost(message, repeated, tag = "1")]
+    pub violations: Vec<precondition_failure::Violation>,
+}
+
+impl_name!(PreconditionFailure, "PreconditionFailure");
+
+pub mod precondition_failure {
+    /// A single precondition failure.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Violation {
+        /// The type of precondition failure, e.g., `TOS`.
+        #[prost(string, tag = "1")]
+        pub r#type: String,
+
+        /// The subject, relative to the type, that failed.
+        #[prost(string, tag = "2")]
+        pub subject: String,
+
+        /// A description of how the precondition failed.
+        #[prost(string, tag = "3")]
+        pub description: String,
+    }
+}
+
+impl PreconditionFailure {
+    /// Appends a precondition violation of type `type` on `subject`.
+    #[must_use]
+    pub fn with_violation(
+        mut self,
+        r#type: impl Into<String>,
+        subject: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.violations.push(precondition_failure::Violation {
+            r#type: r#type.into(),
+            subject: subject.into(),
+            description: description.into(),
+        });
+        self
+    }
+}
+
+/// Describes violations in a client request.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BadRequest {
+    /// Describes all violations in a client request.
+    #[prost(message, repeated, tag = "1")]
+    pub field_violations: Vec<bad_request::FieldViolation>,
+}
+
+impl_name!(BadRequest, "BadRequest");
+
+pub mod bad_request {
+    /// A single bad request field.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FieldViolation {
+        /// A path to the offending field, e.g., `container.mounts[2].source`.
+        #[prost(string, tag = "1")]
+        pub field: String,
+
+        /// A description of why the field is bad.
+        #[prost(string, tag = "2")]
+        pub description: String,
+    }
+}
+
+impl BadRequest {
+    /// Appends a violation on the request `field`.
+    #[must_use]
+    pub fn with_field_violation(
+        mut self,
+        field: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.field_violations.push(bad_request::FieldViolation {
+            field: field.into(),
+            description: description.into(),
+        });
+        self
+    }
+}
+
+/// Contains metadata about the request that clients can attach when filing a bug.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RequestInfo {
+    /// An opaque string that should only be interpreted by the service generating it.
+    #[prost(string, tag = "1")]
+    pub request_id: String,
+
+    /// Any data that was used to serve this request.
+    #[prost(string, tag = "2")]
+    pub serving_data: String,
+}
+
+impl_name!(RequestInfo, "RequestInfo");
+
+impl RequestInfo {
+    /// Creates a request info with the `request_id` of the failed request.
+    #[must_use]
+    pub fn new(request_id: impl Into<String>, serving_data: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            serving_data: serving_data.into(),
+        }
+    }
+}
+
+/// Describes the resource that is being accessed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ResourceInfo {
+    /// The type of the resource being accessed, e.g., `container`.
+    #[prost(string, tag = "1")]
+    pub resource_type: String,
+
+    /// The name of the resource being accessed.
+    #[prost(string, tag = "2")]
+    pub resource_name: String,
+
+    /// The owner of the resource (optional).
+    #[prost(string, tag = "3")]
+    pub owner: String,
+
+    /// Describes what error is encountered when accessing this resource.
+    #[prost(string, tag = "4")]
+    pub description: String,
+}
+
+impl_name!(ResourceInfo, "ResourceInfo");
+
+impl ResourceInfo {
+    /// Creates a resource info for the resource of type `resource_type` named `resource_name`.
+    #[must_use]
+    pub fn new(
+        resource_type: impl Into<String>,
+        resource_name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        Self {
+            resource_type: resource_type.into(),
+            resource_name: resource_name.into(),
+            owner: String::new(),
+            description: description.into(),
+        }
+    }
+
+    /// Sets the owner of the resource.
+    #[must_use]
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = owner.into();
+        self
+    }
+}
+
+/// Provides links to documentation or for performing an out of band action.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Help {
+    /// URL(s) pointing to additional information on handling the current error.
+    #[prost(message, repeated, tag = "1")]
+    pub links: Vec<help::Link>,
+}
+
+impl_name!(Help, "Help");
+
+pub mod help {
+    /// Describes a URL link.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Link {
+        /// Describes what the link offers.
+        #[prost(string, tag = "1")]
+        pub description: String,
+
+        /// The URL of the link.
+        #[prost(string, tag = "2")]
+        pub url: String,
+    }
+}
+
+impl Help {
+    /// Appends a link to documentation, with a `description` of what it offers.
+    #[must_use]
+    pub fn with_link(mut self, description: impl Into<String>, url: impl Into<String>) -> Self {
+        self.links.push(help::Link {
+            description: description.into(),
+            url: url.into(),
+        });
+        self
+    }
+}
+
+/// Provides a localized error message that is safe to return to the user.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LocalizedMessage {
+    /// The locale used, following the specification defined in BCP 47, e.g., `en-US`.
+    #[prost(string, tag = "1")]
+    pub locale: String,
+
+    /// The localized error message in the above locale.
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+impl_name!(LocalizedMessage, "LocalizedMessage");
+
+impl LocalizedMessage {
+    /// Creates a `message` localized in `locale`, e.g., `en-US`.
+    #[must_use]
+    pub fn new(locale: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            message: message.into(),
+        }
+    }
+}
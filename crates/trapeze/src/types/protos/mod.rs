@@ -1,10 +1,12 @@
 pub mod code;
 pub mod data;
+pub mod error_details;
 pub mod key_value;
 pub mod raw_bytes;
 pub mod request;
 pub mod response;
 pub mod status;
+pub mod trailer;
 
 pub use code::Code;
 pub use data::Data;
@@ -12,3 +14,4 @@ pub use key_value::KeyValue;
 pub use request::Request;
 pub use response::Response;
 pub use status::Status;
+pub use trailer::Trailer;
@@ -5,13 +5,53 @@ use prost::encoding::{DecodeContext, WireType};
 
 use crate::types::encoding::{DecodeError, Decodeable, Encodeable, InvalidInput};
 
-#[derive(Clone, Debug, Default)]
+/// An undecoded protobuf payload.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct RawBytes(Bytes);
 
+// A type that can be used as the payload of a request, response or data message
+pub trait Payload: ProstField + Encodeable + Decodeable + Default {}
+
+impl<T: ProstField + Encodeable + Decodeable + Default> Payload for T {}
+
 impl RawBytes {
+    pub fn new(bytes: impl Into<Bytes>) -> Self {
+        Self(bytes.into())
+    }
+
+    pub fn encode<Msg: prost::Message>(msg: &Msg) -> Self {
+        Self(msg.encode_to_vec().into())
+    }
+
     pub fn decode<Msg: prost::Message + Default>(&self) -> Result<Msg, DecodeError> {
         Ok(Msg::decode(self.0.clone())?)
     }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl From<Bytes> for RawBytes {
+    fn from(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Vec<u8>> for RawBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl From<RawBytes> for Bytes {
+    fn from(bytes: RawBytes) -> Self {
+        bytes.0
+    }
 }
 
 impl Buf for RawBytes {
@@ -26,6 +66,10 @@ impl Buf for RawBytes {
     fn advance(&mut self, cnt: usize) {
         self.0.advance(cnt);
     }
+
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        self.0.copy_to_bytes(len)
+    }
 }
 
 pub trait ProstField: Send + Sync + Debug {
@@ -1,11 +1,13 @@
 use super::raw_bytes::{ProstField, RawBytes};
 use crate::types::message::{Message, MessageType};
-use crate::types::protos::Status;
+use crate::types::protos::{KeyValue, Status};
 
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct Response<Payload: ProstField + Default = RawBytes> {
     pub status: Option<Status>,
     pub payload: Payload,
+    // Only sent to clients that accept response metadata
+    pub metadata: Vec<KeyValue>,
 }
 
 impl<Payload: ProstField + Default> Message for Response<Payload> {
@@ -17,6 +19,7 @@ impl Response<()> {
         Self {
             status: Some(status),
             payload: (),
+            metadata: Vec::new(),
         }
     }
 }
@@ -24,7 +27,18 @@ impl Response<()> {
 impl<Payload: ProstField + Default> Response<Payload> {
     pub fn ok(payload: Payload) -> Self {
         let status = None;
-        Self { status, payload }
+        let metadata = Vec::new();
+        Self {
+            status,
+            payload,
+            metadata,
+        }
+    }
+
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: Vec<KeyValue>) -> Self {
+        self.metadata = metadata;
+        self
     }
 }
 
@@ -38,6 +52,9 @@ pub struct Response<Payload: ProstMessage + Default> {
 
     #[prost(message, required)]
     pub payload: Payload,
+
+    #[prost(message, repeated)]
+    pub metadata: Vec<KeyValue>,
 }
 ```
 */
@@ -50,6 +67,9 @@ impl<Payload: ProstField + Default> ::prost::Message for Response<Payload> {
             ::prost::encoding::message::encode(1u32, msg, buf);
         }
         self.payload.encode(2u32, buf);
+        for msg in &self.metadata {
+            ::prost::encoding::message::encode(3u32, msg, buf);
+        }
     }
     #[allow(unused_variables)]
     fn merge_field(
@@ -81,6 +101,15 @@ impl<Payload: ProstField + Default> ::prost::Message for Response<Payload> {
                     error
                 })
             }
+            3u32 => {
+                let value = &mut self.metadata;
+                ::prost::encoding::message::merge_repeated(wire_type, value, buf, ctx).map_err(
+                    |mut error| {
+                        error.push(STRUCT_NAME, "metadata");
+                        error
+                    },
+                )
+            }
             _ => ::prost::encoding::skip_field(wire_type, tag, buf, ctx),
         }
     }
@@ -91,9 +120,11 @@ impl<Payload: ProstField + Default> ::prost::Message for Response<Payload> {
             .as_ref()
             .map_or(0, |msg| ::prost::encoding::message::encoded_len(1u32, msg))
             + self.payload.encoded_len(2u32)
+            + ::prost::encoding::message::encoded_len_repeated(3u32, &self.metadata)
     }
     fn clear(&mut self) {
         self.status = ::core::option::Option::None;
         self.payload.clear();
+        self.metadata.clear();
     }
 }
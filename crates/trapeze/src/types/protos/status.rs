@@ -1,8 +1,10 @@
 use std::fmt::Display;
 
+use prost::Name;
 pub use prost_types::Any;
 use thiserror::Error;
 
+use super::error_details::DebugInfo;
 pub use super::Code;
 use crate::io::SendError;
 use crate::types::encoding::DecodeError;
@@ -73,18 +75,34 @@ impl Status {
         Self::invalid_argument(format!("Stream `{stream_id}` is already in use"))
     }
 
-    pub(crate) fn invalid_stream_id(stream_id: u32) -> Self {
-        Self::invalid_argument(format!("Stream id must be odd, found `{stream_id}`"))
+    pub(crate) fn invalid_stream_id(stream_id: u32, parity: u32) -> Self {
+        let expected = if parity == 1 { "odd" } else { "even" };
+        Self::invalid_argument(format!("Stream id must be {expected}, found `{stream_id}`"))
     }
 
     pub(crate) fn stream_closed(stream_id: u32) -> Self {
         Self::invalid_argument(format!("Channel on stream `{stream_id}` is closed"))
     }
 
+    // The connection closed while the call was in flight, so the server may have handled it
     pub(crate) fn channel_closed() -> Self {
         Self::aborted("Channel closed")
     }
 
+    // The connection closed before the request was sent, so the server never saw it
+    pub(crate) fn connection_closed() -> Self {
+        Self::unavailable("Connection closed")
+    }
+
+    // The peer didn't answer a keepalive probe in time, so the connection was torn down
+    pub(crate) fn keepalive_timeout() -> Self {
+        Self::unavailable("Keepalive probe timed out")
+    }
+
+    pub(crate) fn call_finished() -> Self {
+        Self::failed_precondition("The call already finished")
+    }
+
     pub(crate) fn expected_request(stream_id: u32, ty: MessageType) -> Self {
         const TY: MessageType = MessageType::Request;
         let msg = format!("Invalid message type {ty:?} on stream `{stream_id}`, expected {TY:?}",);
@@ -101,6 +119,15 @@ impl Status {
         Status::invalid_argument(format!("Error decoding message: {err}"))
     }
 
+    pub(crate) fn unsupported_compression() -> Self {
+        Status::unimplemented("Received a compressed payload without a supported compression")
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub(crate) fn failed_to_decompress(err: std::io::Error) -> Self {
+        Status::invalid_argument(format!("Error decompressing payload: {err}"))
+    }
+
     #[allow(clippy::needless_pass_by_value)]
     pub(crate) fn send_error(err: SendError) -> Self {
         Status::internal(format!("Error sending message: {err}"))
@@ -119,6 +146,54 @@ impl Status {
     pub fn from_error(err: impl std::error::Error) -> Self {
         Status::unknown(err.to_string())
     }
+
+    /// Finds a `Status` in the cause chain of `error`, including `error` itself.
+    pub fn find_in_chain<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a Status> {
+        let mut cause = Some(error);
+        while let Some(err) = cause {
+            if let Some(status) = err.downcast_ref::<Status>() {
+                return Some(status);
+            }
+            cause = err.source();
+        }
+        None
+    }
+
+    // Keeps the causes of `error` in a `DebugInfo` detail, one stack entry per cause.
+    fn with_cause_chain(self, error: &(dyn std::error::Error + 'static)) -> Self {
+        let mut causes = vec![];
+        let mut cause = error.source();
+        while let Some(err) = cause {
+            causes.push(err.to_string());
+            cause = err.source();
+        }
+        if causes.is_empty() {
+            return self;
+        }
+        self.with_detail(DebugInfo::new(error.to_string()).with_stack_entries(causes))
+    }
+
+    /// Appends a detail message, e.g., one from `trapeze::error_details`.
+    #[must_use]
+    pub fn with_detail<T: Name>(mut self, detail: T) -> Self {
+        self.details.push(Any {
+            type_url: T::type_url(),
+            value: detail.encode_to_vec(),
+        });
+        self
+    }
+
+    /// Returns the first detail message of type `T`, if any.
+    ///
+    /// Details are matched by the fully qualified name in their type URL,
+    /// regardless of the URL prefix.
+    pub fn get_detail<T: Name + Default>(&self) -> Option<T> {
+        let full_name = T::full_name();
+        self.details
+            .iter()
+            .filter(|any| any.type_url.rsplit('/').next() == Some(full_name.as_str()))
+            .find_map(|any| T::decode(any.value.as_slice()).ok())
+    }
 }
 
 pub trait StatusExt {
@@ -140,16 +215,61 @@ impl<T, E: ToString> StatusExt for Result<T, E> {
     }
 }
 
+/// The code is derived from the `ErrorKind` of the error,
+/// and its cause chain is kept in a `DebugInfo` detail.
+/// An io error wrapping a `Status` is converted back to that `Status`.
 impl From<std::io::Error> for Status {
     fn from(error: std::io::Error) -> Self {
-        Status::internal(error.to_string())
+        let error = match error.downcast::<Status>() {
+            Ok(status) => return status,
+            Err(error) => error,
+        };
+        let code = Code::from(error.kind());
+        Status::new(code, error.to_string()).with_cause_chain(&error)
     }
 }
 
+/// If the chain of the error contains a `Status`, its code and details are used.
+/// Otherwise, if it contains an `std::io::Error`, the code is derived from its `ErrorKind`.
+/// The cause chain is kept in a `DebugInfo` detail.
+///
+/// ```
+/// # use anyhow::Context as _;
+/// # use trapeze::{Code, Status};
+/// let err = Err::<(), _>(Status::not_found("No such container"))
+///     .context("Failed to stop container")
+///     .unwrap_err();
+///
+/// let status = Status::from(err);
+/// assert_eq!(status.code(), Code::NotFound);
+/// assert_eq!(status.message, "Failed to stop container");
+/// ```
 #[cfg(feature = "anyhow")]
 impl From<anyhow::Error> for Status {
     fn from(error: anyhow::Error) -> Self {
-        Status::internal(error.to_string())
+        // Downcasting also succeeds through added context, which we don't want to lose
+        let error = match error.chain().nth(1) {
+            None => match error.downcast::<Status>() {
+                Ok(status) => return status,
+                Err(error) => error,
+            },
+            Some(_) => error,
+        };
+
+        let mut status = error
+            .chain()
+            .find_map(|cause| {
+                if let Some(status) = cause.downcast_ref::<Status>() {
+                    Some(status.clone())
+                } else {
+                    let code = Code::from(cause.downcast_ref::<std::io::Error>()?.kind());
+                    Some(Status::new(code, ""))
+                }
+            })
+            .unwrap_or_else(|| Status::internal(""));
+
+        status.message = error.to_string();
+        status.with_cause_chain(error.as_ref())
     }
 }
 
@@ -159,3 +279,128 @@ fn code_to_str(code: i32) -> &'static str {
     };
     code.as_str_name()
 }
+
+#[cfg(test)]
+mod tests {
+    use prost::Message as _;
+
+    use super::*;
+    use crate::error_details::{BadRequest, DebugInfo, ErrorInfo};
+
+    #[test]
+    fn gets_details_by_type() {
+        let status = Status::new(Code::InvalidArgument, "bad request")
+            .with_detail(ErrorInfo::new("BAD", "example.com"))
+            .with_detail(BadRequest::default().with_field_violation("name", "empty"));
+
+        let info: ErrorInfo = status.get_detail().unwrap();
+        assert_eq!(info.reason, "BAD");
+
+        let request: BadRequest = status.get_detail().unwrap();
+        assert_eq!(request.field_violations[0].field, "name");
+    }
+
+    #[test]
+    fn gets_details_with_any_type_url_prefix() {
+        let info = ErrorInfo::new("BAD", "example.com");
+        let mut status = Status::new(Code::InvalidArgument, "bad request");
+        status.details.push(Any {
+            type_url: "example.com/types/google.rpc.ErrorInfo".into(),
+            value: info.encode_to_vec(),
+        });
+
+        assert_eq!(status.get_detail::<ErrorInfo>(), Some(info));
+        assert_eq!(status.get_detail::<BadRequest>(), None);
+    }
+
+    #[test]
+    fn ignores_details_with_other_names() {
+        let mut status = Status::new(Code::InvalidArgument, "bad request");
+        status.details.push(Any {
+            type_url: "type.googleapis.com/other.ErrorInfo".into(),
+            value: ErrorInfo::new("BAD", "example.com").encode_to_vec(),
+        });
+
+        assert_eq!(status.get_detail::<ErrorInfo>(), None);
+    }
+
+    #[test]
+    fn converts_io_errors_by_kind() {
+        let err = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message, "no such file");
+        assert!(status.details.is_empty());
+
+        let err = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert_eq!(Status::from(err).code(), Code::Unavailable);
+    }
+
+    #[test]
+    fn unwraps_status_from_io_errors() {
+        let err = std::io::Error::other(Status::new(Code::AlreadyExists, "exists"));
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::AlreadyExists);
+        assert_eq!(status.message, "exists");
+    }
+
+    #[test]
+    fn keeps_io_error_causes() {
+        let cause = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let err = std::io::Error::new(std::io::ErrorKind::ConnectionReset, Wrapped(cause));
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let info: DebugInfo = status.get_detail().unwrap();
+        assert_eq!(info.detail, "wrapped: timed out");
+        assert_eq!(info.stack_entries, ["timed out"]);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn converts_anyhow_errors() {
+        use anyhow::Context as _;
+
+        let err = anyhow::anyhow!("something failed");
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message, "something failed");
+        assert!(status.details.is_empty());
+
+        let status = Status::from(anyhow::Error::new(Status::new(Code::NotFound, "missing")));
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message, "missing");
+
+        let err = Err::<(), _>(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            .context("Failed to open")
+            .unwrap_err();
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message, "Failed to open");
+
+        let info: DebugInfo = status.get_detail().unwrap();
+        assert_eq!(info.detail, "Failed to open");
+        assert_eq!(info.stack_entries, ["permission denied"]);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn keeps_status_details_through_anyhow_context() {
+        use anyhow::Context as _;
+
+        let source = Status::new(Code::FailedPrecondition, "not ready")
+            .with_detail(ErrorInfo::new("NOT_READY", "example.com"));
+        let err = Err::<(), _>(source).context("Failed to start").unwrap_err();
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message, "Failed to start");
+
+        let info: ErrorInfo = status.get_detail().unwrap();
+        assert_eq!(info.reason, "NOT_READY");
+    }
+
+    // An error with a source, to check that cause chains are kept
+    #[derive(Debug, Error)]
+    #[error("wrapped: {0}")]
+    struct Wrapped(#[source] std::io::Error);
+}
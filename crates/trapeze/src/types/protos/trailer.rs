@@ -0,0 +1,8 @@
+use crate::types::protos::KeyValue;
+
+// The payload of the frame closing a server stream, when response metadata was negotiated
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Trailer {
+    #[prost(message, repeated)]
+    pub metadata: Vec<KeyValue>,
+}
@@ -0,0 +1,58 @@
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+use trapeze::raw;
+use trapeze::Server;
+
+mod common;
+
+use common::{bytes, listen, text, unary};
+
+// A server recording the kind of every accept error
+fn server(errors: &Arc<Mutex<Vec<ErrorKind>>>) -> Server {
+    let errors = errors.clone();
+    Server::new()
+        .register_method(
+            "/test.Service/Echo",
+            raw::unary(|payload| async move { Ok(payload) }),
+        )
+        .on_accept_error(move |err| errors.lock().unwrap().push(err.kind()))
+}
+
+#[tokio::test]
+async fn keeps_accepting_after_errors() {
+    let errors = Arc::default();
+    let (connector, _server) = listen(server(&errors));
+
+    connector.fail(IoError::from(ErrorKind::ConnectionAborted));
+    connector.fail(IoError::from(ErrorKind::ConnectionReset));
+    let client = connector.connect();
+
+    let response = unary(&client, "/test.Service/Echo", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "x");
+    assert_eq!(
+        *errors.lock().unwrap(),
+        [ErrorKind::ConnectionAborted, ErrorKind::ConnectionReset]
+    );
+}
+
+#[tokio::test]
+async fn backs_off_on_resource_errors() {
+    let errors = Arc::default();
+    let (connector, _server) = listen(server(&errors));
+
+    // e.g., too many open files
+    let start = Instant::now();
+    connector.fail(IoError::new(ErrorKind::Other, "too many open files"));
+    connector.fail(IoError::new(ErrorKind::Other, "too many open files"));
+    let client = connector.connect();
+
+    let response = unary(&client, "/test.Service/Echo", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "x");
+    assert_eq!(errors.lock().unwrap().len(), 2);
+
+    // the delay doubles after consecutive errors, starting at 5ms
+    assert!(start.elapsed() >= Duration::from_millis(15));
+}
@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{stream, StreamExt as _};
+use tokio::time::{sleep, Instant};
+use trapeze::balancer::{BalancePolicy, Balancer};
+use trapeze::raw::{self, RawBytes};
+use trapeze::{get_context, Client, ClientExt as _, Code, RequestHandler as _, Result, Status};
+
+mod common;
+
+use common::{bytes, closed, connect, text};
+
+// An endpoint answering with its name after `delay`, or failing if `fail` is set
+fn endpoint(name: &'static str, delay: Duration, fail: bool) -> Client {
+    connect(|server| {
+        server
+            .register_method(
+                "/test.Service/Name",
+                raw::unary(move |_| async move {
+                    sleep(delay).await;
+                    if fail {
+                        return Err(Status::unavailable(format!("{name} failed")));
+                    }
+                    Ok(bytes(name))
+                }),
+            )
+            .register_method("/test.Service/Hang", raw::unary(|_| std::future::pending()))
+            .register_method(
+                "/test.Service/Stream",
+                raw::server_streaming(move |_| {
+                    stream::once(async move {
+                        sleep(delay).await;
+                        Ok(bytes(name))
+                    })
+                }),
+            )
+            .register_method(
+                "/test.Service/Metadata",
+                raw::unary(|key| async move {
+                    let ctx = get_context();
+                    let values = ctx.metadata.get(&text(&key)).cloned().unwrap_or_default();
+                    Ok(bytes(format!(
+                        "{} {}",
+                        values.join(","),
+                        ctx.timeout.as_nanos()
+                    )))
+                }),
+            );
+    })
+}
+
+fn fast(name: &'static str) -> Client {
+    endpoint(name, Duration::ZERO, false)
+}
+
+async fn call(balancer: &Balancer, method: &str) -> Result<String> {
+    let response: RawBytes = balancer
+        .handle_unary_request("test.Service".into(), method.into(), bytes(""))
+        .await?;
+    Ok(text(&response))
+}
+
+#[tokio::test]
+async fn uses_endpoints_in_turn() {
+    let balancer = Balancer::new([fast("a"), fast("b"), fast("c")]);
+
+    let mut names = vec![];
+    for _ in 0..6 {
+        names.push(call(&balancer, "Name").await.unwrap());
+    }
+    assert_eq!(names, ["a", "b", "c", "a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn uses_least_outstanding_endpoint() {
+    let balancer = Balancer::new([fast("a"), fast("b")]).policy(BalancePolicy::LeastOutstanding);
+
+    // the first call goes to the first endpoint, and stays in progress
+    let hanging = tokio::spawn({
+        let balancer = balancer.clone();
+        async move { call(&balancer, "Hang").await }
+    });
+    sleep(Duration::from_millis(50)).await;
+
+    for _ in 0..3 {
+        assert_eq!(call(&balancer, "Name").await.unwrap(), "b");
+    }
+    hanging.abort();
+}
+
+#[tokio::test]
+async fn fails_without_endpoints() {
+    let balancer = Balancer::<Client>::new([]);
+
+    let status = call(&balancer, "Name").await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+// An endpoint whose connection is closed
+async fn closed_endpoint() -> Client {
+    let (client, server) = tokio::io::duplex(1 << 10);
+    drop(server);
+    let client = Client::new(client);
+    closed(&client).await;
+    client
+}
+
+#[tokio::test]
+async fn skips_closed_endpoints() {
+    let endpoints = [fast("a"), closed_endpoint().await, fast("c")];
+    let balancer = Balancer::new(endpoints);
+
+    let mut names = vec![];
+    for _ in 0..4 {
+        names.push(call(&balancer, "Name").await.unwrap());
+    }
+    assert_eq!(names, ["a", "c", "c", "a"]);
+
+    let balancer = balancer.policy(BalancePolicy::LeastOutstanding);
+    for _ in 0..4 {
+        assert_ne!(call(&balancer, "Name").await.unwrap(), "b");
+    }
+}
+
+#[tokio::test]
+async fn fails_when_all_endpoints_are_closed() {
+    let balancer = Balancer::new([closed_endpoint().await, closed_endpoint().await]);
+    assert!(balancer.is_closed());
+
+    let status = call(&balancer, "Name").await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn connects_to_available_addresses() {
+    let address = format!("unix://@trapeze-test-balancer-{}", std::process::id());
+    let missing = format!("{address}-missing");
+    let _server = trapeze::Server::new()
+        .register_method(
+            "/test.Service/Name",
+            raw::unary(|_| async move { Ok(bytes("a")) }),
+        )
+        .bind(&address)
+        .await
+        .unwrap();
+
+    let balancer = Balancer::connect([&missing, &address]).await.unwrap();
+    assert_eq!(balancer.endpoints().count(), 1);
+    assert_eq!(call(&balancer, "Name").await.unwrap(), "a");
+
+    assert!(Balancer::connect([&missing]).await.is_err());
+}
+
+#[tokio::test]
+async fn hedges_slow_unary_calls() {
+    let slow = endpoint("slow", Duration::from_secs(10), false);
+    let balancer = Balancer::new([slow, fast("fast")]).hedging(Duration::from_millis(10));
+
+    let start = Instant::now();
+    assert_eq!(call(&balancer, "Name").await.unwrap(), "fast");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn hedges_with_second_endpoint_after_failure() {
+    let failing = endpoint("failing", Duration::from_millis(50), true);
+    let slower = endpoint("slower", Duration::from_millis(100), false);
+    let balancer = Balancer::new([failing, slower]).hedging(Duration::from_millis(10));
+
+    assert_eq!(call(&balancer, "Name").await.unwrap(), "slower");
+}
+
+#[tokio::test]
+async fn does_not_hedge_calls_finished_before_delay() {
+    let failing = endpoint("failing", Duration::ZERO, true);
+    let balancer = Balancer::new([failing, fast("b")]).hedging(Duration::from_secs(1));
+
+    let status = call(&balancer, "Name").await.unwrap_err();
+    assert_eq!(status.message, "failing failed");
+}
+
+#[tokio::test]
+async fn does_not_hedge_streaming_calls() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let counting = |name: &'static str| {
+        let calls = calls.clone();
+        connect(move |server| {
+            server.register_method(
+                "/test.Service/Stream",
+                raw::server_streaming(move |_| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    stream::once(async move {
+                        sleep(Duration::from_millis(100)).await;
+                        Ok(bytes(name))
+                    })
+                }),
+            );
+        })
+    };
+    let balancer = Balancer::new([counting("a"), counting("b")]).hedging(Duration::from_millis(10));
+
+    let responses: Vec<RawBytes> = balancer
+        .handle_server_streaming_request("test.Service".into(), "Stream".into(), bytes(""))
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn streams_from_a_single_endpoint() {
+    let balancer = Balancer::new([fast("a"), fast("b")]);
+
+    for name in ["a", "b"] {
+        let responses: Vec<String> = balancer
+            .handle_server_streaming_request::<_, RawBytes>(
+                "test.Service".into(),
+                "Stream".into(),
+                bytes(""),
+            )
+            .map(|response| text(&response.unwrap()))
+            .collect()
+            .await;
+        assert_eq!(responses, [name]);
+    }
+}
+
+#[tokio::test]
+async fn adds_balancer_context_to_endpoints() {
+    let endpoint = fast("a")
+        .with_metadata([("key", "endpoint")])
+        .with_timeout(Duration::from_secs(30));
+    let balancer = Balancer::new([endpoint]);
+
+    let response: RawBytes = balancer
+        .handle_unary_request("test.Service".into(), "Metadata".into(), bytes("key"))
+        .await
+        .unwrap();
+    let nanos = Duration::from_secs(30).as_nanos();
+    assert_eq!(text(&response), format!("endpoint {nanos}"));
+
+    let balancer = balancer
+        .with_metadata([("key", "balancer")])
+        .with_timeout(Duration::from_secs(5));
+    let response: RawBytes = balancer
+        .handle_unary_request("test.Service".into(), "Metadata".into(), bytes("key"))
+        .await
+        .unwrap();
+    let nanos = Duration::from_secs(5).as_nanos();
+    assert_eq!(text(&response), format!("endpoint,balancer {nanos}"));
+}
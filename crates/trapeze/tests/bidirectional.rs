@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use tokio::io::duplex;
+use tokio::time::timeout;
+use trapeze::raw::{self, RawBytes};
+use trapeze::{get_context, Client, Code, Server, ServerConnection, Status};
+
+mod common;
+
+use common::{bytes, listen, text, unary};
+
+// Registers a method on the client end of the connection
+fn register_client_methods(server: &mut ServerConnection) {
+    server
+        .register_method(
+            "/test.Client/Name",
+            raw::unary(|payload| async move { Ok(bytes(format!("client {}", text(&payload)))) }),
+        )
+        .register_method(
+            "/test.Client/Big",
+            raw::unary(|payload| async move {
+                let len = text(&payload).parse().unwrap();
+                Ok(bytes(vec![7; len]))
+            }),
+        );
+}
+
+// Calls back into the client of the connection
+async fn call_client(path: &str, payload: RawBytes) -> trapeze::Result<RawBytes> {
+    let ctx = get_context();
+    let Some(client) = ctx.connection().client() else {
+        return Err(Status::failed_precondition(
+            "Not a bidirectional connection",
+        ));
+    };
+    unary(client, path, payload).await
+}
+
+fn server() -> Server {
+    Server::new()
+        .bidirectional()
+        .register_method(
+            "/test.Server/Name",
+            raw::unary(|payload| async move {
+                let name = call_client("/test.Client/Name", payload).await?;
+                Ok(bytes(format!("server, {}", text(&name))))
+            }),
+        )
+        .register_method(
+            "/test.Server/BigLength",
+            raw::unary(|payload| async move {
+                let big = call_client("/test.Client/Big", payload).await?;
+                Ok(bytes(big.as_bytes().len().to_string()))
+            }),
+        )
+}
+
+fn connect_bidirectional(server: Server) -> (Client, trapeze::ServerHandle) {
+    let (connector, handle) = listen(server);
+    let (client, mut client_server) = Client::bidirectional(connector.open());
+    register_client_methods(&mut client_server);
+    tokio::spawn(async move { client_server.start().await });
+    (client, handle)
+}
+
+#[tokio::test]
+async fn calls_the_client_from_handlers() {
+    let (client, _server) = connect_bidirectional(server());
+
+    let response = unary(&client, "/test.Server/Name", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "server, client x");
+}
+
+#[tokio::test]
+async fn shares_a_connection_between_both_ends() {
+    let (a, b) = duplex(1 << 20);
+
+    let (client, mut client_server) = Client::bidirectional(a);
+    register_client_methods(&mut client_server);
+    tokio::spawn(async move { client_server.start().await });
+
+    let (mut server, server_client) = ServerConnection::bidirectional(b);
+    server.register_method(
+        "/test.Server/Echo",
+        raw::unary(|payload| async move { Ok(payload) }),
+    );
+    tokio::spawn(async move { server.start().await });
+
+    // calls in both directions, interleaved on the same connection
+    let (from_client, from_server) = tokio::join!(
+        unary(&client, "/test.Server/Echo", bytes("a")),
+        unary(&server_client, "/test.Client/Name", bytes("b")),
+    );
+    assert_eq!(text(&from_client.unwrap()), "a");
+    assert_eq!(text(&from_server.unwrap()), "client b");
+}
+
+#[tokio::test]
+async fn rejects_streams_with_wrong_parity() {
+    let (a, b) = duplex(1 << 20);
+
+    // the client half of a bidirectional server opens even streams, which a
+    // plain server expects to be odd
+    let (_server, client) = ServerConnection::bidirectional(a);
+    let mut other = ServerConnection::new(b);
+    other.register_method(
+        "/test.Server/Echo",
+        raw::unary(|payload| async move { Ok(payload) }),
+    );
+    tokio::spawn(async move { other.start().await });
+
+    let status = timeout(
+        Duration::from_secs(5),
+        unary(&client, "/test.Server/Echo", bytes("")),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn splits_large_responses_to_the_server() {
+    let (client, _server) = connect_bidirectional(server().max_message_size(16 << 20));
+
+    // the response to the call from the server is larger than a frame
+    let len = 5 << 20;
+    let response = unary(&client, "/test.Server/BigLength", bytes(len.to_string())).await;
+    assert_eq!(text(&response.unwrap()), len.to_string());
+}
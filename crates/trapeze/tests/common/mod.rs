@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+
+use std::io::Result as IoResult;
+
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt as _};
+use tokio::io::{duplex, DuplexStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use trapeze::raw::RawBytes;
+use trapeze::transport::{Connection, Listener};
+use trapeze::{Client, RequestHandler as _, Result, Server, ServerConnection, ServerHandle};
+
+/// Connects a client to a server connection set up by `setup`, over an in-memory connection.
+pub fn connect(setup: impl FnOnce(&mut ServerConnection)) -> Client {
+    let (client, server) = duplex(1 << 20);
+    let mut server = ServerConnection::new(server);
+    setup(&mut server);
+    tokio::spawn(async move { server.start().await });
+    Client::new(client)
+}
+
+/// Starts `server` on a listener accepting the in-memory connections of the returned `Connector`.
+pub fn listen(server: Server) -> (Connector, ServerHandle) {
+    let (tx, rx) = unbounded_channel();
+    let handle = server.start(TestListener(rx));
+    (Connector(tx), handle)
+}
+
+/// Opens connections to a server started with `listen`.
+pub struct Connector(UnboundedSender<IoResult<Box<dyn Connection>>>);
+
+impl Connector {
+    pub fn connect(&self) -> Client {
+        Client::new(self.open())
+    }
+
+    /// Opens a connection, returning the client end.
+    pub fn open(&self) -> DuplexStream {
+        let (client, server) = duplex(1 << 20);
+        self.0.send(Ok(Box::new(server))).unwrap();
+        client
+    }
+
+    /// Makes the listener fail to accept a connection with `err`.
+    pub fn fail(&self, err: std::io::Error) {
+        self.0.send(Err(err)).unwrap();
+    }
+}
+
+struct TestListener(UnboundedReceiver<IoResult<Box<dyn Connection>>>);
+
+#[async_trait]
+impl Listener for TestListener {
+    async fn accept(&mut self) -> IoResult<Box<dyn Connection>> {
+        match self.0.recv().await {
+            Some(conn) => conn,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Waits for the connection of `client` to close.
+pub async fn closed(client: &Client) {
+    while !client.is_closed() {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+}
+
+pub fn bytes(value: impl Into<Vec<u8>>) -> RawBytes {
+    RawBytes::new(value.into())
+}
+
+pub fn text(payload: &RawBytes) -> String {
+    String::from_utf8(payload.as_bytes().to_vec()).unwrap()
+}
+
+pub async fn unary(client: &Client, path: &str, payload: RawBytes) -> Result<RawBytes> {
+    let (service, method) = split_path(path);
+    client.handle_unary_request(service, method, payload).await
+}
+
+pub fn server_streaming<'a>(
+    client: &'a Client,
+    path: &str,
+    payload: RawBytes,
+) -> impl Stream<Item = Result<RawBytes>> + 'a {
+    let (service, method) = split_path(path);
+    client.handle_server_streaming_request(service, method, payload)
+}
+
+pub async fn client_streaming(
+    client: &Client,
+    path: &str,
+    input: Vec<RawBytes>,
+) -> Result<RawBytes> {
+    let (service, method) = split_path(path);
+    client
+        .handle_client_streaming_request(service, method, stream::iter(input))
+        .await
+}
+
+pub async fn duplex_streaming(
+    client: &Client,
+    path: &str,
+    input: Vec<RawBytes>,
+) -> Vec<Result<RawBytes>> {
+    let (service, method) = split_path(path);
+    client
+        .handle_duplex_streaming_request(service, method, stream::iter(input))
+        .collect()
+        .await
+}
+
+fn split_path(path: &str) -> (String, String) {
+    let (service, method) = path.trim_start_matches('/').split_once('/').unwrap();
+    (service.into(), method.into())
+}
@@ -0,0 +1,110 @@
+#![cfg(any(feature = "gzip", feature = "zstd"))]
+
+use futures::StreamExt as _;
+use trapeze::raw;
+use trapeze::{Client, Code, Compression, Server};
+
+mod common;
+
+use common::{bytes, client_streaming, connect, listen, server_streaming, text, unary};
+
+// Payloads large enough to be sent compressed
+fn payload(part: &str) -> String {
+    part.repeat(1000)
+}
+
+fn client() -> Client {
+    connect(|server| {
+        server
+            .register_method(
+                "/test.Service/Echo",
+                raw::unary(|payload| async move { Ok(payload) }),
+            )
+            .register_method(
+                "/test.Service/Concat",
+                raw::client_streaming(|input| async move {
+                    let parts: Vec<_> = input.map(|part| text(&part)).collect().await;
+                    Ok(bytes(parts.concat()))
+                }),
+            )
+            .register_method(
+                "/test.Service/Repeat",
+                raw::server_streaming(|payload| {
+                    futures::stream::iter([Ok(payload.clone()), Ok(payload)])
+                }),
+            );
+    })
+}
+
+async fn round_trip(client: &Client) {
+    let response = unary(client, "/test.Service/Echo", bytes(payload("a"))).await;
+    assert_eq!(text(&response.unwrap()), payload("a"));
+
+    let input = vec![bytes(payload("a")), bytes("b"), bytes(payload("c"))];
+    let response = client_streaming(client, "/test.Service/Concat", input).await;
+    let expected = [payload("a"), "b".into(), payload("c")].concat();
+    assert_eq!(text(&response.unwrap()), expected);
+
+    let responses: Vec<_> = server_streaming(client, "/test.Service/Repeat", bytes(payload("a")))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, [payload("a"), payload("a")]);
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn round_trips_gzip() {
+    round_trip(&client().with_compression(Compression::Gzip)).await;
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn round_trips_zstd() {
+    round_trip(&client().with_compression(Compression::Zstd)).await;
+}
+
+#[tokio::test]
+async fn compresses_only_responses() {
+    for name in ["gzip", "zstd"] {
+        let Some(compression) = Compression::from_name(name) else {
+            continue;
+        };
+        round_trip(&client().with_response_compression(compression)).await;
+    }
+}
+
+#[tokio::test]
+async fn responds_uncompressed_to_clients_without_compression() {
+    // the client doesn't advertise an encoding, so it can't decompress the responses
+    round_trip(&client()).await;
+}
+
+#[tokio::test]
+async fn limits_decompressed_payloads_to_the_max_message_size() {
+    let compression = ["gzip", "zstd"]
+        .into_iter()
+        .find_map(Compression::from_name)
+        .unwrap();
+    // compresses into a single frame, but is larger than a frame once decompressed
+    let large = vec![7; 6 << 20];
+
+    for (max_message_size, accepted) in [(None, false), (Some(16 << 20), true)] {
+        let mut server = Server::new().register_method(
+            "/test.Service/Len",
+            raw::unary(|payload| async move { Ok(bytes(payload.as_bytes().len().to_string())) }),
+        );
+        if let Some(max_message_size) = max_message_size {
+            server = server.max_message_size(max_message_size);
+        }
+        let (connector, _server) = listen(server);
+        let client = Client::new(connector.open()).with_compression(compression);
+
+        let response = unary(&client, "/test.Service/Len", bytes(large.clone())).await;
+        if accepted {
+            assert_eq!(text(&response.unwrap()), large.len().to_string());
+        } else {
+            assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+        }
+    }
+}
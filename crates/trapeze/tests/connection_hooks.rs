@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::time::timeout;
+use trapeze::raw;
+use trapeze::{get_context, Server};
+
+mod common;
+
+use common::{bytes, listen, text, unary};
+
+// Per-connection state attached by `on_connect`
+struct Session(String);
+
+fn server() -> Server {
+    Server::new()
+        .register_method(
+            "/test.Service/Session",
+            raw::unary(|_| async move {
+                let ctx = get_context();
+                let session = ctx.connection().extensions().get::<Session>().unwrap();
+                Ok(bytes(session.0.clone()))
+            }),
+        )
+        .register_method(
+            "/test.Service/Id",
+            raw::unary(|_| async move { Ok(bytes(get_context().connection().id().to_string())) }),
+        )
+}
+
+#[tokio::test]
+async fn attaches_state_to_connections() {
+    let connections = Arc::new(Mutex::new(0));
+    let server = server().on_connect({
+        let connections = connections.clone();
+        move |conn| {
+            let mut connections = connections.lock().unwrap();
+            *connections += 1;
+            conn.extensions_mut()
+                .insert(Session(format!("session {connections}")));
+        }
+    });
+    let (connector, _server) = listen(server);
+
+    let first = connector.connect();
+    let response = unary(&first, "/test.Service/Session", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "session 1");
+
+    let second = connector.connect();
+    let response = unary(&second, "/test.Service/Session", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "session 2");
+
+    // the state stays with its connection
+    let response = unary(&first, "/test.Service/Session", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "session 1");
+}
+
+#[tokio::test]
+async fn gives_connections_unique_ids() {
+    let (connector, _server) = listen(server());
+
+    let first = connector.connect();
+    let second = connector.connect();
+    let first_id = text(&unary(&first, "/test.Service/Id", bytes("")).await.unwrap());
+    let second_id = text(&unary(&second, "/test.Service/Id", bytes("")).await.unwrap());
+    assert_ne!(first_id, second_id);
+
+    let again = text(&unary(&first, "/test.Service/Id", bytes("")).await.unwrap());
+    assert_eq!(first_id, again);
+}
+
+#[tokio::test]
+async fn reports_disconnections() {
+    let (tx, mut rx) = unbounded_channel();
+    let server = server()
+        .on_connect(|conn| conn.extensions_mut().insert(Session("closing".into())))
+        .on_disconnect(move |conn, err| {
+            let session = conn.extensions().get::<Session>().unwrap().0.clone();
+            let _ = tx.send((conn.id(), session, err.map(|err| err.kind())));
+        });
+    let (connector, _server) = listen(server);
+
+    let client = connector.connect();
+    let id = text(&unary(&client, "/test.Service/Id", bytes("")).await.unwrap());
+    drop(client);
+
+    let (disconnected, session, err) = timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(disconnected.to_string(), id);
+    assert_eq!(session, "closing");
+    assert_eq!(err, None);
+}
@@ -0,0 +1,206 @@
+#![cfg(feature = "reflect")]
+
+use std::time::Duration;
+
+use futures::{stream, StreamExt as _};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    MethodDescriptorProto, ServiceDescriptorProto,
+};
+use tokio::time::timeout;
+use trapeze::raw::{self, RawBytes};
+use trapeze::reflect::{DescriptorPool, DynamicClient, DynamicMessage, Value};
+use trapeze::Code;
+
+mod common;
+
+use common::connect;
+
+// Describes a message with a single field
+fn message(name: &str, field: &str, label: Label, ty: Type) -> DescriptorProto {
+    DescriptorProto {
+        name: Some(name.into()),
+        field: vec![FieldDescriptorProto {
+            name: Some(field.into()),
+            json_name: Some(field.into()),
+            number: Some(1),
+            label: Some(label as i32),
+            r#type: Some(ty as i32),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn method(name: &str, client_streaming: bool, server_streaming: bool) -> MethodDescriptorProto {
+    MethodDescriptorProto {
+        name: Some(name.into()),
+        input_type: Some(".test.Msg".into()),
+        output_type: Some(".test.Msg".into()),
+        client_streaming: Some(client_streaming),
+        server_streaming: Some(server_streaming),
+        ..Default::default()
+    }
+}
+
+fn pool() -> DescriptorPool {
+    let file = FileDescriptorProto {
+        name: Some("test.proto".into()),
+        package: Some("test".into()),
+        syntax: Some("proto3".into()),
+        message_type: vec![
+            // messages sent to client streaming methods are merged into their repeated field
+            message("Msg", "text", Label::Repeated, Type::String),
+            message("Other", "count", Label::Optional, Type::Int32),
+        ],
+        service: vec![ServiceDescriptorProto {
+            name: Some("Echo".into()),
+            method: vec![
+                method("Unary", false, false),
+                method("ServerStreaming", false, true),
+                method("ClientStreaming", true, false),
+                method("DuplexStreaming", true, true),
+            ],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let files = FileDescriptorSet { file: vec![file] };
+    DescriptorPool::from_file_descriptor_set(files).unwrap()
+}
+
+// A client of a raw server echoing the payloads it receives
+fn client() -> DynamicClient {
+    let client = connect(|server| {
+        server
+            .register_method(
+                "/test.Echo/Unary",
+                raw::unary(|payload| async move { Ok(payload) }),
+            )
+            .register_method(
+                "/test.Echo/ServerStreaming",
+                raw::server_streaming(|payload| stream::iter([Ok(payload.clone()), Ok(payload)])),
+            )
+            .register_method(
+                "/test.Echo/ClientStreaming",
+                raw::client_streaming(|input| async move {
+                    let payloads: Vec<_> = input.collect().await;
+                    let payload: Vec<u8> = payloads
+                        .iter()
+                        .flat_map(RawBytes::as_bytes)
+                        .copied()
+                        .collect();
+                    Ok(RawBytes::new(payload))
+                }),
+            )
+            .register_method(
+                "/test.Echo/DuplexStreaming",
+                raw::duplex_streaming(|input| input.map(Ok)),
+            );
+    });
+    DynamicClient::new(client, pool())
+}
+
+fn msg(client: &DynamicClient, texts: &[&str]) -> DynamicMessage {
+    let descriptor = client.pool().get_message_by_name("test.Msg").unwrap();
+    let mut msg = DynamicMessage::new(descriptor);
+    let texts = texts.iter().map(|t| Value::String((*t).into())).collect();
+    msg.set_field_by_name("text", Value::List(texts));
+    msg
+}
+
+#[tokio::test]
+async fn calls_unary_methods() {
+    let client = client();
+
+    let response = client
+        .unary("test.Echo", "Unary", msg(&client, &["a"]))
+        .await;
+    assert_eq!(response.unwrap(), msg(&client, &["a"]));
+}
+
+#[tokio::test]
+async fn calls_streaming_methods() {
+    let client = client();
+
+    let responses: Vec<_> = client
+        .server_streaming("test.Echo", "ServerStreaming", msg(&client, &["a"]))
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(responses, [msg(&client, &["a"]), msg(&client, &["a"])]);
+
+    let input = stream::iter([msg(&client, &["a"]), msg(&client, &["b"])]);
+    let response = client
+        .client_streaming("test.Echo", "ClientStreaming", input)
+        .await;
+    assert_eq!(response.unwrap(), msg(&client, &["a", "b"]));
+
+    let input = stream::iter([msg(&client, &["a"]), msg(&client, &["b"])]);
+    let responses: Vec<_> = client
+        .duplex_streaming("test.Echo", "DuplexStreaming", input)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(responses, [msg(&client, &["a"]), msg(&client, &["b"])]);
+}
+
+#[tokio::test]
+async fn calls_unary_methods_with_open_inputs() {
+    let client = client();
+
+    // the input never finishes
+    let input = stream::iter([msg(&client, &["a"])]).chain(stream::pending());
+    let responses = client.call("test.Echo", "Unary", input).collect::<Vec<_>>();
+    let responses = timeout(Duration::from_secs(5), responses).await.unwrap();
+    assert_eq!(responses, [Ok(msg(&client, &["a"]))]);
+}
+
+#[tokio::test]
+async fn calls_methods_with_json() {
+    let client = client();
+
+    let input = stream::iter([r#"{"text":["a"]}"#.to_string()]);
+    let responses: Vec<_> = client
+        .call_json("test.Echo", "ServerStreaming", input)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(responses, [r#"{"text":["a"]}"#, r#"{"text":["a"]}"#]);
+
+    let input = stream::iter([r#"{"text":["a"]}"#.into(), r#"{"text":["b"]}"#.into()]);
+    let responses: Vec<_> = client
+        .call_json("test.Echo", "ClientStreaming", input)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(responses, [r#"{"text":["a","b"]}"#]);
+}
+
+#[tokio::test]
+async fn rejects_invalid_inputs() {
+    let client = client();
+
+    let descriptor = client.pool().get_message_by_name("test.Other").unwrap();
+    let other = DynamicMessage::new(descriptor);
+    let status = client.unary("test.Echo", "Unary", other).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let input = stream::iter([r#"{"count":1}"#.to_string()]);
+    let responses: Vec<_> = client
+        .call_json("test.Echo", "Unary", input)
+        .collect()
+        .await;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(
+        responses[0].as_ref().unwrap_err().code(),
+        Code::InvalidArgument
+    );
+
+    let status = client
+        .unary("test.Echo", "Missing", msg(&client, &[]))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
@@ -0,0 +1,112 @@
+use futures::StreamExt as _;
+use trapeze::raw::{self, RawCall};
+use trapeze::{Code, Result};
+
+mod common;
+
+use common::{bytes, client_streaming, connect, server_streaming, text, unary};
+
+// Answers any kind of call with the called path, the payload, and the client stream
+async fn describe(mut call: RawCall) -> Result<()> {
+    let mut description = format!(
+        "/{}/{} {}",
+        call.service(),
+        call.method(),
+        text(call.payload())
+    );
+    while let Some(payload) = call.recv().await? {
+        description.push_str(&text(&payload));
+    }
+    call.respond(bytes(description)).await
+}
+
+#[tokio::test]
+async fn handles_unregistered_methods() {
+    let client = connect(|server| {
+        server.fallback(describe);
+    });
+
+    let response = unary(&client, "/test.Missing/Method", bytes("payload")).await;
+    assert_eq!(text(&response.unwrap()), "/test.Missing/Method payload");
+
+    let input = vec![bytes("a"), bytes("b")];
+    let response = client_streaming(&client, "/test.Missing/Stream", input).await;
+    assert_eq!(text(&response.unwrap()), "/test.Missing/Stream ab");
+}
+
+#[tokio::test]
+async fn sends_server_streams() {
+    let client = connect(|server| {
+        server.fallback(|call| async move {
+            for _ in 0..2 {
+                call.send(call.payload().clone()).await?;
+            }
+            call.close().await
+        });
+    });
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Missing/Stream", bytes("x"))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, ["x", "x"]);
+}
+
+#[tokio::test]
+async fn prefers_registered_methods() {
+    let client = connect(|server| {
+        server
+            .fallback(describe)
+            .register_method(
+                "/test.Service/*",
+                raw::unary(|_| async move { Ok(bytes("service")) }),
+            )
+            .register_method(
+                "/test.Service/Exact",
+                raw::unary(|_| async move { Ok(bytes("exact")) }),
+            );
+    });
+
+    let response = unary(&client, "/test.Service/Exact", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "exact");
+
+    let response = unary(&client, "/test.Service/Other", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "service");
+
+    let response = unary(&client, "/test.Other/Method", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "/test.Other/Method ");
+}
+
+#[tokio::test]
+async fn fails_with_status_from_fallback() {
+    let client = connect(|server| {
+        server.fallback(|call| async move {
+            Err(trapeze::Status::new(
+                Code::Unimplemented,
+                format!("{} is gone", call.method()),
+            ))
+        });
+    });
+
+    let status = unary(&client, "/test.Missing/Method", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unimplemented);
+    assert_eq!(status.message, "Method is gone");
+}
+
+#[tokio::test]
+async fn rejects_unregistered_methods_without_fallback() {
+    let client = connect(|server| {
+        server.register_method(
+            "/test.Service/Method",
+            raw::unary(|payload| async move { Ok(payload) }),
+        );
+    });
+
+    let status = unary(&client, "/test.Service/Other", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.message, "/test.Service/Other is not supported");
+}
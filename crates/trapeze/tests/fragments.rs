@@ -0,0 +1,56 @@
+use trapeze::raw;
+use trapeze::{Client, Code, Server};
+
+mod common;
+
+use common::{bytes, listen, text, unary};
+
+// Larger than a frame (4 MiB)
+const LARGE: usize = 6 << 20;
+
+fn server() -> Server {
+    Server::new().register_method(
+        "/test.Service/Echo",
+        raw::unary(|payload| async move { Ok(payload) }),
+    )
+}
+
+fn client(server: Server) -> (Client, trapeze::ServerHandle) {
+    let (connector, handle) = listen(server);
+    let client = Client::builder()
+        .max_message_size(16 << 20)
+        .build(connector.open());
+    (client, handle)
+}
+
+#[tokio::test]
+async fn splits_large_messages() {
+    let (client, _server) = client(server().max_message_size(16 << 20));
+
+    let response = unary(&client, "/test.Service/Echo", bytes(vec![7; LARGE])).await;
+    assert_eq!(*response.unwrap().as_bytes(), vec![7; LARGE]);
+}
+
+#[tokio::test]
+async fn rejects_fragments_unless_enabled() {
+    let (client, _server) = client(server());
+
+    let status = unary(&client, "/test.Service/Echo", bytes(vec![7; LARGE]))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn rejects_oversized_messages() {
+    let (client, _server) = client(server().max_message_size(5 << 20));
+
+    let status = unary(&client, "/test.Service/Echo", bytes(vec![7; LARGE]))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // the connection keeps working
+    let response = unary(&client, "/test.Service/Echo", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "x");
+}
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+
+use trapeze::raw;
+use trapeze::{get_context, Client, ClientExt as _, Code, ServerConnection, Status};
+
+mod common;
+
+use common::{bytes, connect, text, unary};
+
+// State attached to calls by the interceptors
+struct Principal(String);
+struct Trace(Vec<&'static str>);
+
+fn register(server: &mut ServerConnection) {
+    server.register_method(
+        "/test.Service/Whoami",
+        raw::unary(|_| async move {
+            let ctx = get_context();
+            let principal = ctx.extensions().get::<Principal>().unwrap();
+            let trace = ctx.extensions().get::<Trace>().map(|t| t.0.join(","));
+            Ok(bytes(format!(
+                "{} {}",
+                principal.0,
+                trace.unwrap_or_default()
+            )))
+        }),
+    );
+}
+
+fn authenticate(server: &mut ServerConnection) -> &mut ServerConnection {
+    server.interceptor(|ctx| {
+        let Some(user) = ctx.metadata.get("user").and_then(|v| v.first()).cloned() else {
+            return Err(Status::unauthenticated("Missing user"));
+        };
+        ctx.extensions_mut().insert(Principal(user));
+        Ok(())
+    })
+}
+
+fn client() -> Client {
+    connect(|server| {
+        register(authenticate(server));
+    })
+}
+
+#[tokio::test]
+async fn attaches_state_to_calls() {
+    let client = client().with_metadata([("user", "alice")]);
+
+    let response = unary(&client, "/test.Service/Whoami", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "alice ");
+}
+
+#[tokio::test]
+async fn rejects_calls() {
+    let client = client();
+
+    let status = unary(&client, "/test.Service/Whoami", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message, "Missing user");
+}
+
+#[tokio::test]
+async fn runs_interceptors_in_order() {
+    let client = connect(|server| {
+        authenticate(server)
+            .interceptor(|ctx| {
+                ctx.extensions_mut().insert(Trace(vec!["first"]));
+                Ok(())
+            })
+            .interceptor(|ctx| {
+                let Some(trace) = ctx.extensions().get::<Trace>() else {
+                    return Err(Status::internal("Out of order"));
+                };
+                let mut trace = trace.0.clone();
+                trace.push("second");
+                ctx.extensions_mut().insert(Trace(trace));
+                Ok(())
+            });
+        register(server);
+    })
+    .with_metadata([("user", "bob")]);
+
+    let response = unary(&client, "/test.Service/Whoami", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "bob first,second");
+}
+
+#[tokio::test]
+async fn keeps_state_per_call() {
+    let client = client();
+
+    let alice = client.with_metadata([("user", "alice")]);
+    let bob = client.with_metadata([("user", "bob")]);
+    let response = unary(&alice, "/test.Service/Whoami", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "alice ");
+    let response = unary(&bob, "/test.Service/Whoami", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "bob ");
+}
+
+#[tokio::test]
+async fn reports_stream_lifecycle() {
+    let events = Arc::new(Mutex::new(vec![]));
+    let client = connect(|server| {
+        let opened = events.clone();
+        let closed = events.clone();
+        server
+            .on_stream_open(move |ctx| {
+                opened
+                    .lock()
+                    .unwrap()
+                    .push(format!("open {}", ctx.method()));
+            })
+            .on_stream_close(move |ctx, status| {
+                let code = status.map(Status::code);
+                closed
+                    .lock()
+                    .unwrap()
+                    .push(format!("close {} {code:?}", ctx.method()));
+            })
+            .register_method(
+                "/test.Service/Ok",
+                raw::unary(|payload| async move { Ok(payload) }),
+            )
+            .register_method(
+                "/test.Service/Fail",
+                raw::unary(|_| async move { Err(Status::not_found("Missing")) }),
+            );
+    });
+
+    unary(&client, "/test.Service/Ok", bytes("")).await.unwrap();
+    unary(&client, "/test.Service/Fail", bytes(""))
+        .await
+        .unwrap_err();
+
+    // the close hooks may run after the client received the response
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let mut events = events.lock().unwrap().clone();
+    events.sort();
+    assert_eq!(
+        events,
+        [
+            "close Fail Some(NotFound)",
+            "close Ok None",
+            "open Fail",
+            "open Ok",
+        ]
+    );
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use futures::StreamExt as _;
+use tokio::io::{duplex, AsyncReadExt as _, AsyncWriteExt as _, DuplexStream};
+use tokio::time::{sleep, timeout};
+use trapeze::{Client, Code, ServerConnection};
+
+mod common;
+
+use common::{bytes, closed, server_streaming, unary};
+
+fn client(connection: DuplexStream) -> Client {
+    Client::builder()
+        .keepalive(Duration::from_millis(20), Duration::from_millis(50))
+        .build(connection)
+}
+
+// A server that answers every request with `Code::NotFound`, like servers without keepalive support
+async fn serve_not_found(mut connection: DuplexStream) -> std::io::Result<()> {
+    // A response with the status `{ code: NOT_FOUND }`
+    const RESPONSE: [u8; 4] = [0x0a, 0x02, 0x08, Code::NotFound as u8];
+    loop {
+        let mut header = [0; 10];
+        connection.read_exact(&mut header).await?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let mut message = vec![0; length as usize];
+        connection.read_exact(&mut message).await?;
+
+        let mut frame = (RESPONSE.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&header[4..8]);
+        frame.extend_from_slice(&[2, 0]);
+        frame.extend_from_slice(&RESPONSE);
+        connection.write_all(&frame).await?;
+    }
+}
+
+#[tokio::test]
+async fn fails_calls_when_probes_time_out() {
+    // the server never reads nor answers anything
+    let (connection, _server) = duplex(1 << 20);
+    let client = client(connection);
+
+    let call = unary(&client, "/test.Service/Method", bytes(""));
+    let status = timeout(Duration::from_secs(5), call)
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(status.message, "Keepalive probe timed out");
+    closed(&client).await;
+
+    // calls made after the connection closed fail too
+    let responses: Vec<_> = server_streaming(&client, "/test.Service/Stream", bytes(""))
+        .collect()
+        .await;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].as_ref().unwrap_err().code(), Code::Unavailable);
+}
+
+#[tokio::test]
+async fn fails_streaming_calls_when_probes_time_out() {
+    let (connection, _server) = duplex(1 << 20);
+    let client = client(connection);
+
+    let responses = server_streaming(&client, "/test.Service/Stream", bytes("")).collect();
+    let responses: Vec<_> = timeout(Duration::from_secs(5), responses).await.unwrap();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].as_ref().unwrap_err().code(), Code::Unavailable);
+}
+
+#[tokio::test]
+async fn accepts_any_response_to_probes() {
+    let (connection, server) = duplex(1 << 20);
+    tokio::spawn(serve_not_found(server));
+    let client = client(connection);
+
+    // long enough for several probes to time out
+    sleep(Duration::from_millis(300)).await;
+    assert!(!client.is_closed());
+
+    let status = unary(&client, "/test.Service/Method", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn keeps_connections_to_trapeze_servers() {
+    let (connection, server) = duplex(1 << 20);
+    let mut server = ServerConnection::new(server);
+    tokio::spawn(async move { server.start().await });
+    let client = client(connection);
+
+    sleep(Duration::from_millis(300)).await;
+    assert!(!client.is_closed());
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use tokio::time::{sleep, timeout};
+use trapeze::raw;
+use trapeze::{Code, ExcessConnections, Server};
+
+mod common;
+
+use common::{bytes, closed, listen, text, unary};
+
+fn server() -> Server {
+    Server::new()
+        .register_method(
+            "/test.Service/Echo",
+            raw::unary(|payload| async move { Ok(payload) }),
+        )
+        .register_method(
+            "/test.Service/Sleep",
+            raw::unary(|payload| async move {
+                let millis = text(&payload).parse().unwrap();
+                sleep(Duration::from_millis(millis)).await;
+                Ok(payload)
+            }),
+        )
+}
+
+#[tokio::test]
+async fn closes_idle_connections() {
+    let (connector, _server) = listen(server().max_idle_time(Duration::from_millis(100)));
+    let client = connector.connect();
+
+    unary(&client, "/test.Service/Echo", bytes("x"))
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(5), closed(&client))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn keeps_connections_with_calls_in_progress() {
+    let (connector, _server) = listen(server().max_idle_time(Duration::from_millis(50)));
+    let client = connector.connect();
+
+    let response = unary(&client, "/test.Service/Sleep", bytes("300")).await;
+    assert_eq!(text(&response.unwrap()), "300");
+    assert!(!client.is_closed());
+}
+
+#[tokio::test]
+async fn closes_connections_at_max_age() {
+    let (connector, _server) = listen(server().max_connection_age(Duration::from_millis(100)));
+    let client = connector.connect();
+
+    // the call in progress finishes past the max age of the connection
+    let call = tokio::spawn({
+        let client = client.clone();
+        async move { unary(&client, "/test.Service/Sleep", bytes("300")).await }
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let status = unary(&client, "/test.Service/Echo", bytes("x"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+
+    assert_eq!(text(&call.await.unwrap().unwrap()), "300");
+    timeout(Duration::from_secs(5), closed(&client))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn refuses_excess_connections() {
+    let (connector, _server) = listen(server().max_connections(1));
+    let first = connector.connect();
+    unary(&first, "/test.Service/Echo", bytes("x"))
+        .await
+        .unwrap();
+
+    let second = connector.connect();
+    timeout(Duration::from_secs(5), closed(&second))
+        .await
+        .unwrap();
+
+    // the open connection is not affected
+    unary(&first, "/test.Service/Echo", bytes("x"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn waits_for_connection_slots() {
+    let server = server()
+        .max_connections(1)
+        .excess_connections(ExcessConnections::Wait);
+    let (connector, _server) = listen(server);
+    let first = connector.connect();
+    unary(&first, "/test.Service/Echo", bytes("x"))
+        .await
+        .unwrap();
+
+    let second = connector.connect();
+    let call = tokio::spawn({
+        let second = second.clone();
+        async move { unary(&second, "/test.Service/Echo", bytes("y")).await }
+    });
+
+    // the second connection is only served once the first one closes
+    sleep(Duration::from_millis(100)).await;
+    assert!(!call.is_finished());
+    drop(first);
+
+    let response = timeout(Duration::from_secs(5), call).await.unwrap();
+    assert_eq!(text(&response.unwrap().unwrap()), "y");
+}
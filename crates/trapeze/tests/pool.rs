@@ -0,0 +1,96 @@
+#![cfg(target_os = "linux")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::time::sleep;
+use trapeze::raw::{self, RawBytes};
+use trapeze::{Client, ClientPool, Code, RequestHandler as _, Result, Server, ServerHandle};
+
+mod common;
+
+use common::{bytes, text};
+
+// A unique abstract socket address, so tests don't need to clean up after themselves
+fn address() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("unix://@trapeze-test-pool-{}-{n}", std::process::id())
+}
+
+async fn serve(address: &str) -> ServerHandle {
+    Server::new()
+        .max_message_size(16 << 20)
+        .register_method(
+            "/test.Service/Echo",
+            raw::unary(|payload| async move { Ok(payload) }),
+        )
+        .bind(address)
+        .await
+        .unwrap()
+}
+
+async fn echo(pool: &ClientPool, payload: RawBytes) -> Result<RawBytes> {
+    pool.handle_unary_request("test.Service".into(), "Echo".into(), payload)
+        .await
+}
+
+async fn wait_for_closed_connections(pool: &ClientPool) {
+    while pool.open_connections() > 0 {
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn opens_connections() {
+    let address = address();
+    let _server = serve(&address).await;
+
+    let pool = ClientPool::connect(&address, 3).await.unwrap();
+    assert_eq!(pool.open_connections(), 3);
+
+    for _ in 0..6 {
+        let response = echo(&pool, bytes("x")).await.unwrap();
+        assert_eq!(text(&response), "x");
+    }
+}
+
+#[tokio::test]
+async fn replaces_closed_connections() {
+    let address = address();
+    let server = serve(&address).await;
+
+    let pool = ClientPool::connect(&address, 2).await.unwrap();
+    server.controller().terminate();
+    wait_for_closed_connections(&pool).await;
+
+    // calls fail while the server is away
+    let status = echo(&pool, bytes("x")).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+
+    let _server = serve(&address).await;
+    for _ in 0..2 {
+        let response = echo(&pool, bytes("x")).await.unwrap();
+        assert_eq!(text(&response), "x");
+    }
+    assert_eq!(pool.open_connections(), 2);
+}
+
+#[tokio::test]
+async fn keeps_builder_options_when_reconnecting() {
+    let address = address();
+    let server = serve(&address).await;
+
+    let builder = Client::builder().max_message_size(16 << 20);
+    let pool = ClientPool::with_builder(builder, &address, 1)
+        .await
+        .unwrap();
+    server.controller().terminate();
+    wait_for_closed_connections(&pool).await;
+    let _server = serve(&address).await;
+
+    // messages larger than a frame only fit if the new connection accepts them
+    let payload = bytes(vec![7; 5 << 20]);
+    let response = echo(&pool, payload.clone()).await.unwrap();
+    assert_eq!(response.as_bytes(), payload.as_bytes());
+}
@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use futures::{stream, StreamExt as _};
+use trapeze::proxy::Proxy;
+use trapeze::raw::{self, RawBytes};
+use trapeze::{get_context, Client, ClientExt as _, Code, Server, ServerConnection, Status};
+
+mod common;
+
+use common::{
+    bytes, client_streaming, connect, duplex_streaming, listen, server_streaming, text, unary,
+};
+
+// An upstream server answering with its name, so tests can tell which upstream handled a call
+fn upstream(name: &'static str) -> Client {
+    connect(|server| {
+        register_upstream(server, name);
+    })
+}
+
+fn register_upstream(server: &mut ServerConnection, name: &'static str) {
+    server
+        .register_method(
+            "*",
+            raw::unary(move |payload| async move {
+                let ctx = get_context();
+                let path = format!("/{}/{}", ctx.service(), ctx.method());
+                Ok(bytes(format!("{name} {path} {}", text(&payload))))
+            }),
+        )
+        .register_method(
+            "/test.Upstream/Repeat",
+            raw::server_streaming(|payload| {
+                let count = text(&payload).parse().unwrap();
+                stream::repeat(payload).take(count).map(Ok)
+            }),
+        )
+        .register_method(
+            "/test.Upstream/Concat",
+            raw::client_streaming(|input| async move {
+                let parts: Vec<_> = input.map(|payload| text(&payload)).collect().await;
+                Ok(bytes(parts.concat()))
+            }),
+        )
+        .register_method(
+            "/test.Upstream/Echo",
+            raw::duplex_streaming(|input| input.map(Ok)),
+        )
+        .register_method(
+            "/test.Upstream/Metadata",
+            raw::unary(|payload| async move {
+                let mut values = get_context()
+                    .metadata
+                    .get(&text(&payload))
+                    .cloned()
+                    .unwrap_or_default();
+                values.sort();
+                Ok(bytes(values.join(",")))
+            }),
+        )
+        .register_method(
+            "/test.Upstream/Timeout",
+            raw::unary(|_| async move { Ok(bytes(get_context().timeout.as_nanos().to_string())) }),
+        )
+        .register_method(
+            "/test.Upstream/Fail",
+            raw::unary(|_| async move { Err(Status::new(Code::NotFound, "no such thing")) }),
+        );
+}
+
+fn proxy(proxy: Proxy) -> Client {
+    connect(|server| {
+        server.register_proxy(proxy);
+    })
+}
+
+#[tokio::test]
+async fn forwards_unary_calls() {
+    let client = proxy(Proxy::new().route("test.Upstream", upstream("a")));
+
+    let response = unary(&client, "/test.Upstream/Hello", bytes("world")).await;
+    assert_eq!(text(&response.unwrap()), "a /test.Upstream/Hello world");
+}
+
+#[tokio::test]
+async fn forwards_streaming_calls() {
+    let client = proxy(Proxy::new().route("test.Upstream", upstream("a")));
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Upstream/Repeat", bytes("3"))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, ["3", "3", "3"]);
+
+    let input = vec![bytes("a"), bytes("b"), bytes("c")];
+    let response = client_streaming(&client, "/test.Upstream/Concat", input).await;
+    assert_eq!(text(&response.unwrap()), "abc");
+
+    let input = vec![bytes("x"), bytes("y")];
+    let responses: Vec<_> = duplex_streaming(&client, "/test.Upstream/Echo", input)
+        .await
+        .into_iter()
+        .map(|response| text(&response.unwrap()))
+        .collect();
+    assert_eq!(responses, ["x", "y"]);
+}
+
+#[tokio::test]
+async fn routes_by_service_name() {
+    let client = proxy(
+        Proxy::new()
+            .route("grpc.Health", upstream("health"))
+            .route("", upstream("default")),
+    );
+
+    let response = unary(&client, "/grpc.Health/Check", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "health /grpc.Health/Check ");
+
+    // routes stop at the service boundary, instead of matching any service with the same prefix
+    let response = unary(&client, "/grpc.HealthCheck/Check", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "default /grpc.HealthCheck/Check ");
+}
+
+#[tokio::test]
+async fn rejects_calls_without_route() {
+    let client = proxy(Proxy::new().route("grpc.Health", upstream("health")));
+
+    let status = unary(&client, "/other.Service/Check", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn merges_upstream_metadata() {
+    let upstream = upstream("a").with_metadata([("key", "upstream"), ("other", "1")]);
+    let client = proxy(Proxy::new().route("", upstream));
+    let client = client.with_metadata([("key", "client")]);
+
+    let response = unary(&client, "/test.Upstream/Metadata", bytes("key")).await;
+    assert_eq!(text(&response.unwrap()), "client,upstream");
+
+    let response = unary(&client, "/test.Upstream/Metadata", bytes("other")).await;
+    assert_eq!(text(&response.unwrap()), "1");
+}
+
+#[tokio::test]
+async fn falls_back_to_upstream_timeout() {
+    let upstream = upstream("a").with_timeout(Duration::from_secs(30));
+    let client = proxy(Proxy::new().route("", upstream));
+
+    let response = unary(&client, "/test.Upstream/Timeout", bytes("")).await;
+    let nanos = Duration::from_secs(30).as_nanos().to_string();
+    assert_eq!(text(&response.unwrap()), nanos);
+
+    let client = client.with_timeout(Duration::from_secs(5));
+    let response = unary(&client, "/test.Upstream/Timeout", bytes("")).await;
+    let nanos = Duration::from_secs(5).as_nanos().to_string();
+    assert_eq!(text(&response.unwrap()), nanos);
+}
+
+#[tokio::test]
+async fn forwards_upstream_errors() {
+    let client = proxy(Proxy::new().route("", upstream("a")));
+
+    let status = unary(&client, "/test.Upstream/Fail", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.message, "no such thing");
+}
+
+#[tokio::test]
+async fn fails_when_upstream_is_gone() {
+    let (client, server) = tokio::io::duplex(1 << 10);
+    drop(server);
+    let upstream = Client::new(client);
+    while !upstream.is_closed() {
+        tokio::task::yield_now().await;
+    }
+    let client = proxy(Proxy::new().route("", upstream));
+
+    let status = unary(&client, "/test.Upstream/Hello", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+#[tokio::test]
+async fn forwards_with_raw_forward_method() {
+    let client = connect(|server| {
+        server.register_method("/test.Upstream/*", trapeze::proxy::forward(upstream("a")));
+    });
+
+    let response: RawBytes = unary(&client, "/test.Upstream/Hi", bytes("there"))
+        .await
+        .unwrap();
+    assert_eq!(text(&response), "a /test.Upstream/Hi there");
+}
+
+#[tokio::test]
+async fn replaces_the_fallback_with_the_default_route() {
+    let client = connect(|server| {
+        server
+            .fallback(|call| async move { call.respond(bytes("fallback")).await })
+            .register_proxy(Proxy::new().route("", upstream("a")));
+    });
+    let response = unary(&client, "/test.Upstream/Hello", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "a /test.Upstream/Hello ");
+
+    let client = connect(|server| {
+        server
+            .register_proxy(Proxy::new().route("", upstream("a")))
+            .fallback(|call| async move { call.respond(bytes("fallback")).await });
+    });
+    let response = unary(&client, "/test.Upstream/Hello", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "fallback");
+}
+
+#[tokio::test]
+async fn forwards_large_messages_accepted_by_the_client() {
+    // Larger than a frame (4 MiB)
+    const LARGE: usize = 6 << 20;
+
+    let (upstream, server) = tokio::io::duplex(1 << 20);
+    let mut server = ServerConnection::new(server);
+    server.register_method(
+        "/test.Upstream/Large",
+        raw::unary(|_| async move { Ok(bytes(vec![7; LARGE])) }),
+    );
+    tokio::spawn(async move { server.start().await });
+    let upstream = Client::builder().max_message_size(16 << 20).build(upstream);
+
+    let (connector, _server) =
+        listen(Server::new().register_proxy(Proxy::new().route("", upstream)));
+
+    let client = Client::builder()
+        .max_message_size(16 << 20)
+        .build(connector.open());
+    let response = unary(&client, "/test.Upstream/Large", bytes("")).await;
+    assert_eq!(*response.unwrap().as_bytes(), vec![7; LARGE]);
+
+    // neither the upstream server nor the proxy send messages larger than the client accepts
+    let client = Client::builder()
+        .max_message_size(5 << 20)
+        .build(connector.open());
+    let status = unary(&client, "/test.Upstream/Large", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+
+    let client = connector.connect();
+    let status = unary(&client, "/test.Upstream/Large", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+}
@@ -0,0 +1,90 @@
+use futures::{stream, StreamExt as _};
+use trapeze::raw::{self, RawBytes};
+use trapeze::{get_context, Client, Code, Status};
+
+mod common;
+
+use common::{bytes, client_streaming, connect, duplex_streaming, server_streaming, text, unary};
+
+fn client() -> Client {
+    connect(|server| {
+        server
+            .register_method(
+                "/test.Raw/Unary",
+                raw::unary(|payload| async move {
+                    let ctx = get_context();
+                    let path = format!("/{}/{} ", ctx.service(), ctx.method());
+                    Ok(bytes([path.as_bytes(), payload.as_bytes()].concat()))
+                }),
+            )
+            .register_method(
+                "/test.Raw/ServerStreaming",
+                raw::server_streaming(|payload| {
+                    let chars: Vec<_> = text(&payload)
+                        .chars()
+                        .map(|c| Ok(bytes(c.to_string())))
+                        .collect();
+                    stream::iter(chars)
+                }),
+            )
+            .register_method(
+                "/test.Raw/ClientStreaming",
+                raw::client_streaming(|input| async move {
+                    let parts: Vec<_> = input.map(|part| text(&part)).collect().await;
+                    Ok(bytes(parts.concat()))
+                }),
+            )
+            .register_method(
+                "/test.Raw/DuplexStreaming",
+                raw::duplex_streaming(|input| {
+                    input.map(|payload| Ok(bytes(text(&payload).to_uppercase())))
+                }),
+            )
+            .register_method(
+                "/test.Raw/Fail",
+                raw::unary(|_| async move { Err(Status::new(Code::Unavailable, "Failed")) }),
+            );
+    })
+}
+
+#[tokio::test]
+async fn round_trips_unary_calls() {
+    let client = client();
+
+    let response = unary(&client, "/test.Raw/Unary", bytes("payload")).await;
+    assert_eq!(text(&response.unwrap()), "/test.Raw/Unary payload");
+
+    // payloads are not decoded, so they don't need to be valid protobuf
+    let invalid = RawBytes::new(vec![0xff, 0xff, 0xff]);
+    let response = unary(&client, "/test.Raw/Unary", invalid).await.unwrap();
+    assert!(response.as_bytes().ends_with(&[0xff, 0xff, 0xff]));
+
+    let status = unary(&client, "/test.Raw/Fail", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(status.message, "Failed");
+}
+
+#[tokio::test]
+async fn round_trips_streaming_calls() {
+    let client = client();
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Raw/ServerStreaming", bytes("abc"))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, ["a", "b", "c"]);
+
+    let input = vec![bytes("a"), bytes("b"), bytes("c")];
+    let response = client_streaming(&client, "/test.Raw/ClientStreaming", input).await;
+    assert_eq!(text(&response.unwrap()), "abc");
+
+    let input = vec![bytes("a"), bytes("b")];
+    let responses: Vec<_> = duplex_streaming(&client, "/test.Raw/DuplexStreaming", input)
+        .await
+        .into_iter()
+        .map(|response| text(&response.unwrap()))
+        .collect();
+    assert_eq!(responses, ["A", "B"]);
+}
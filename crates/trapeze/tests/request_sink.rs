@@ -0,0 +1,83 @@
+use futures::{SinkExt, StreamExt as _};
+use trapeze::raw::{self, RawBytes};
+use trapeze::{Client, Code, RequestHandler as _, Status};
+
+mod common;
+
+use common::{bytes, connect, text};
+
+fn client() -> Client {
+    connect(|server| {
+        server
+            .register_method(
+                "/test.Service/Concat",
+                raw::client_streaming(|input| async move {
+                    let parts: Vec<_> = input.map(|part| text(&part)).collect().await;
+                    Ok(bytes(parts.concat()))
+                }),
+            )
+            .register_method(
+                "/test.Service/Echo",
+                raw::duplex_streaming(|input| input.map(Ok)),
+            )
+            .register_method(
+                "/test.Service/Fail",
+                raw::client_streaming(|_| async move { Err(Status::not_found("Missing")) }),
+            );
+    })
+}
+
+#[tokio::test]
+async fn sends_pushed_messages() {
+    let client = client();
+
+    let (mut sink, response) =
+        client.open_client_streaming::<RawBytes, RawBytes>("test.Service".into(), "Concat".into());
+    sink.send(bytes("a")).await.unwrap();
+    // through the `Sink` implementation
+    SinkExt::send(&mut sink, bytes("b")).await.unwrap();
+    sink.close_send();
+
+    assert_eq!(text(&response.await.unwrap()), "ab");
+}
+
+#[tokio::test]
+async fn streams_responses_while_sending() {
+    let client = client();
+
+    let (mut sink, responses) =
+        client.open_duplex_streaming::<RawBytes, RawBytes>("test.Service".into(), "Echo".into());
+    let mut responses = Box::pin(responses);
+    for message in ["a", "b"] {
+        sink.send(bytes(message)).await.unwrap();
+        let response = responses.next().await.unwrap();
+        assert_eq!(text(&response.unwrap()), message);
+    }
+    sink.close_send();
+    assert!(responses.next().await.is_none());
+}
+
+#[tokio::test]
+async fn reports_how_the_call_finished() {
+    let client = client();
+
+    let (sink, response) =
+        client.open_client_streaming::<RawBytes, RawBytes>("test.Service".into(), "Fail".into());
+    assert_eq!(response.await.unwrap_err().code(), Code::NotFound);
+    assert!(sink.is_finished());
+
+    let status = sink.send(bytes("late")).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn fails_after_close_send() {
+    let client = client();
+
+    let (mut sink, _response) =
+        client.open_client_streaming::<RawBytes, RawBytes>("test.Service".into(), "Concat".into());
+    sink.close_send();
+
+    let status = sink.send(bytes("late")).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
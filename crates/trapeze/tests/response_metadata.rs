@@ -0,0 +1,112 @@
+use futures::{stream, StreamExt as _};
+use trapeze::raw;
+use trapeze::{get_context, Code, ResponseMetadata, ServerConnection, Status};
+
+mod common;
+
+use common::{bytes, connect, server_streaming, text, unary};
+
+fn register(server: &mut ServerConnection) {
+    server
+        .register_method(
+            "/test.Service/Unary",
+            raw::unary(|payload| async move {
+                get_context().response_metadata().insert("key", "unary");
+                Ok(payload)
+            }),
+        )
+        .register_method(
+            "/test.Service/Fail",
+            raw::unary(|_| async move {
+                get_context().response_metadata().insert("key", "fail");
+                Err(Status::new(Code::NotFound, "not found"))
+            }),
+        )
+        .register_method(
+            "/test.Service/Stream",
+            raw::server_streaming(|payload| {
+                get_context().response_metadata().insert("key", "stream");
+                stream::iter([Ok(payload.clone()), Ok(payload)])
+            }),
+        );
+}
+
+#[tokio::test]
+async fn receives_metadata_with_unary_responses() {
+    let metadata = ResponseMetadata::new();
+    let client = connect(register).with_response_metadata(metadata.clone());
+
+    let response = unary(&client, "/test.Service/Unary", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "x");
+    assert_eq!(metadata.get("key"), ["unary"]);
+}
+
+#[tokio::test]
+async fn receives_metadata_with_errors() {
+    let metadata = ResponseMetadata::new();
+    let client = connect(register).with_response_metadata(metadata.clone());
+
+    let status = unary(&client, "/test.Service/Fail", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(metadata.get("key"), ["fail"]);
+}
+
+#[tokio::test]
+async fn receives_metadata_with_stream_trailers() {
+    let metadata = ResponseMetadata::new();
+    let client = connect(register).with_response_metadata(metadata.clone());
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Service/Stream", bytes("x"))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, ["x", "x"]);
+    assert_eq!(metadata.get("key"), ["stream"]);
+}
+
+#[tokio::test]
+async fn accumulates_metadata_across_calls() {
+    let metadata = ResponseMetadata::new();
+    let client = connect(register).with_response_metadata(metadata.clone());
+
+    for _ in 0..2 {
+        unary(&client, "/test.Service/Unary", bytes(""))
+            .await
+            .unwrap();
+    }
+    assert_eq!(metadata.get("key"), ["unary", "unary"]);
+
+    let taken = metadata.take();
+    assert_eq!(taken.get("key").unwrap(), &["unary", "unary"]);
+    assert!(metadata.get("key").is_empty());
+}
+
+#[tokio::test]
+async fn omits_metadata_unless_accepted() {
+    let client = connect(|server| {
+        register(server);
+        server.register_method(
+            "/test.Service/Accepts",
+            raw::unary(|_| async move {
+                let ctx = get_context();
+                Ok(bytes(
+                    ctx.metadata.keys().cloned().collect::<Vec<_>>().join(","),
+                ))
+            }),
+        );
+    });
+
+    // Streams without response metadata still finish cleanly for clients that don't ask for it
+    let responses: Vec<_> = server_streaming(&client, "/test.Service/Stream", bytes("x"))
+        .map(|response| text(&response.unwrap()))
+        .collect()
+        .await;
+    assert_eq!(responses, ["x", "x"]);
+
+    // The reserved key is not visible to handlers
+    let client = client.with_response_metadata(ResponseMetadata::new());
+    let response = unary(&client, "/test.Service/Accepts", bytes("")).await;
+    assert_eq!(text(&response.unwrap()), "");
+}
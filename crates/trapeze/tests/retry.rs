@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{stream, StreamExt as _};
+use trapeze::raw;
+use trapeze::retry::RetryPolicy;
+use trapeze::{Client, ClientExt as _, Code, ServerConnection, Status};
+
+mod common;
+
+use common::{bytes, client_streaming, connect, server_streaming, text, unary};
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new()
+        .max_attempts(3)
+        .backoff(Duration::from_millis(1), Duration::from_millis(1))
+}
+
+// Registers methods failing with `Unavailable` until they have been called `failures` times
+fn register(server: &mut ServerConnection, calls: &Arc<AtomicU32>, failures: u32) {
+    let unary_calls = calls.clone();
+    let stream_calls = calls.clone();
+    let client_stream_calls = calls.clone();
+    server
+        .register_method(
+            "/test.Service/Unary",
+            raw::unary(move |payload| {
+                let call = unary_calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if call < failures {
+                        return Err(Status::unavailable("not yet"));
+                    }
+                    Ok(payload)
+                }
+            }),
+        )
+        .register_method(
+            "/test.Service/Stream",
+            raw::server_streaming(move |payload| {
+                let call = stream_calls.fetch_add(1, Ordering::SeqCst);
+                let fail = Err(Status::unavailable("interrupted"));
+                // fails after the first response on every call after the `failures` first ones
+                if call < failures {
+                    stream::iter(vec![fail])
+                } else {
+                    stream::iter(vec![Ok(payload), fail])
+                }
+            }),
+        )
+        .register_method(
+            "/test.Service/ClientStream",
+            raw::client_streaming(move |input| {
+                client_stream_calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let count = input.count().await;
+                    Err(Status::unavailable(format!("received {count}")))
+                }
+            }),
+        );
+}
+
+#[tokio::test]
+async fn retries_unary_calls() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let client = connect(|server| register(server, &calls, 2)).with_retry_policy(policy());
+
+    let response = unary(&client, "/test.Service/Unary", bytes("x")).await;
+    assert_eq!(text(&response.unwrap()), "x");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let client = connect(|server| register(server, &calls, 5)).with_retry_policy(policy());
+
+    let status = unary(&client, "/test.Service/Unary", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn uses_method_policies() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let policy = policy().method("/test.Service/Unary", RetryPolicy::disabled());
+    let client = connect(|server| register(server, &calls, 2)).with_retry_policy(policy);
+
+    let status = unary(&client, "/test.Service/Unary", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn stops_retrying_at_timeout() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let policy = policy().backoff(Duration::from_secs(10), Duration::from_secs(10));
+    let client = connect(|server| register(server, &calls, 5))
+        .with_retry_policy(policy)
+        .with_timeout(Duration::from_secs(1));
+
+    let status = unary(&client, "/test.Service/Unary", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn retries_streams_until_a_response_is_received() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let client =
+        connect(|server| register(server, &calls, 1)).with_retry_policy(policy().streaming(true));
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Service/Stream", bytes("x"))
+        .collect()
+        .await;
+
+    // the first attempt failed before any response and was retried,
+    // the second one failed after a response, and was not retried
+    assert_eq!(responses.len(), 2);
+    assert_eq!(text(responses[0].as_ref().unwrap()), "x");
+    assert_eq!(responses[1].as_ref().unwrap_err().code(), Code::Unavailable);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn does_not_retry_streams_after_sending_input() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let client =
+        connect(|server| register(server, &calls, 0)).with_retry_policy(policy().streaming(true));
+
+    let input = vec![bytes("a"), bytes("b")];
+    let status = client_streaming(&client, "/test.Service/ClientStream", input)
+        .await
+        .unwrap_err();
+    assert_eq!(status.message, "received 2");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn does_not_retry_streams_unless_enabled() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let client = connect(|server| register(server, &calls, 1)).with_retry_policy(policy());
+
+    let responses: Vec<_> = server_streaming(&client, "/test.Service/Stream", bytes("x"))
+        .collect()
+        .await;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn fails_with_unavailable_on_closed_connections() {
+    let (client, server) = tokio::io::duplex(1 << 10);
+    drop(server);
+    let client = Client::new(client).with_retry_policy(policy());
+    while !client.is_closed() {
+        tokio::task::yield_now().await;
+    }
+
+    let status = unary(&client, "/test.Service/Unary", bytes(""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+#[tokio::test]
+async fn does_not_retry_calls_interrupted_by_the_connection_closing() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let (client, server) = tokio::io::duplex(1 << 10);
+    let mut server = ServerConnection::new(server);
+    let handler_calls = calls.clone();
+    server.register_method(
+        "/test.Service/Hang",
+        raw::unary(move |_| {
+            handler_calls.fetch_add(1, Ordering::SeqCst);
+            std::future::pending()
+        }),
+    );
+    let server = tokio::spawn(async move { server.start().await });
+    let client = Client::new(client).with_retry_policy(policy());
+
+    let call = tokio::spawn({
+        let client = client.clone();
+        async move { unary(&client, "/test.Service/Hang", bytes("")).await }
+    });
+    while calls.load(Ordering::SeqCst) == 0 {
+        tokio::task::yield_now().await;
+    }
+    server.abort();
+
+    let status = call.await.unwrap().unwrap_err();
+    assert_eq!(status.code(), Code::Aborted);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
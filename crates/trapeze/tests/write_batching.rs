@@ -0,0 +1,48 @@
+use futures::future::join_all;
+use trapeze::raw;
+use trapeze::{Client, Server};
+
+mod common;
+
+use common::{bytes, listen, text, unary};
+
+// Makes many concurrent calls, with payloads both smaller and larger than the batches
+async fn concurrent_calls(max_write_batch: usize) {
+    let server = Server::new()
+        .max_write_batch(max_write_batch)
+        .register_method(
+            "/test.Service/Echo",
+            raw::unary(|payload| async move { Ok(payload) }),
+        );
+    let (connector, _server) = listen(server);
+    let client = Client::builder()
+        .max_write_batch(max_write_batch)
+        .build(connector.open());
+
+    let payloads: Vec<_> = (0..64)
+        .map(|i| i.to_string().repeat(if i % 8 == 0 { 10_000 } else { 1 }))
+        .collect();
+    let calls = payloads
+        .iter()
+        .map(|payload| unary(&client, "/test.Service/Echo", bytes(payload.as_str())));
+    let responses = join_all(calls).await;
+
+    for (response, payload) in responses.into_iter().zip(&payloads) {
+        assert_eq!(&text(&response.unwrap()), payload);
+    }
+}
+
+#[tokio::test]
+async fn batches_writes_by_default() {
+    concurrent_calls(64 << 10).await;
+}
+
+#[tokio::test]
+async fn writes_frames_on_their_own() {
+    concurrent_calls(0).await;
+}
+
+#[tokio::test]
+async fn splits_batches_larger_than_the_limit() {
+    concurrent_calls(100).await;
+}